@@ -1,9 +1,13 @@
-use crate::token::{Annotation, Loc, Token, TokenKind};
+use crate::bigint::BigInt;
+use crate::token::{Annotation, Float, Loc, Token, TokenKind};
 use std::iter::Peekable;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AstKind {
     Number(u64),
+    BigNumber(BigInt),
+    Float(Float),
+    Bool(bool),
     UniOperator {
         operator: UniOperator,
         tree: Box<Ast>,
@@ -13,13 +17,62 @@ pub enum AstKind {
         lhs: Box<Ast>,
         rhs: Box<Ast>,
     },
+    Variable(String),
+    Let {
+        name: Identifier,
+        value: Box<Ast>,
+    },
+    /// `fn name(params) = body`, binds `name` to the function
+    FnDef {
+        name: Identifier,
+        params: Vec<Identifier>,
+        body: Box<Ast>,
+    },
+    /// `fn(params) = body`
+    Lambda {
+        params: Vec<Identifier>,
+        body: Box<Ast>,
+    },
+    Call {
+        callee: Box<Ast>,
+        args: Vec<Ast>,
+    },
+    If {
+        cond: Box<Ast>,
+        then_branch: Box<Ast>,
+        else_branch: Box<Ast>,
+    },
+    /// statements separated by `;` or line breaks, evaluating to the last one
+    Program(Vec<Ast>),
+    /// a part of the input that failed to parse, kept so the rest of the tree is usable
+    Error,
 }
 pub type Ast = Annotation<AstKind>;
+pub type Identifier = Annotation<String>;
+
+/// The parameters of a function definition or lambda as written, separated by `, `.
+pub(crate) fn param_names(params: &[Identifier]) -> String {
+    let names: Vec<&str> = params.iter().map(|param| param.value.as_str()).collect();
+    names.join(", ")
+}
+
 impl Ast {
     fn number(num: u64, loc: Loc) -> Self {
         Self::new(AstKind::Number(num), loc)
     }
 
+    fn big_number(num: BigInt, loc: Loc) -> Self {
+        Self::new(AstKind::BigNumber(num), loc)
+    }
+
+    fn float(num: Float, loc: Loc) -> Self {
+        Self::new(AstKind::Float(num), loc)
+    }
+
+    fn bool(value: bool, loc: Loc) -> Self {
+        Self::new(AstKind::Bool(value), loc)
+    }
+
     fn uni_operator(op: UniOperator, tree: Ast, loc: Loc) -> Self {
         Self::new(
             AstKind::UniOperator {
@@ -40,6 +93,70 @@ impl Ast {
             loc,
         )
     }
+
+    fn variable(name: String, loc: Loc) -> Self {
+        Self::new(AstKind::Variable(name), loc)
+    }
+
+    fn let_(name: Identifier, value: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::Let {
+                name,
+                value: Box::new(value),
+            },
+            loc,
+        )
+    }
+
+    fn fn_def(name: Identifier, params: Vec<Identifier>, body: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::FnDef {
+                name,
+                params,
+                body: Box::new(body),
+            },
+            loc,
+        )
+    }
+
+    fn lambda(params: Vec<Identifier>, body: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::Lambda {
+                params,
+                body: Box::new(body),
+            },
+            loc,
+        )
+    }
+
+    fn call(callee: Ast, args: Vec<Ast>, loc: Loc) -> Self {
+        Self::new(
+            AstKind::Call {
+                callee: Box::new(callee),
+                args,
+            },
+            loc,
+        )
+    }
+
+    fn if_(cond: Ast, then_branch: Ast, else_branch: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::If {
+                cond: Box::new(cond),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            },
+            loc,
+        )
+    }
+
+    fn program(stmts: Vec<Ast>, loc: Loc) -> Self {
+        Self::new(AstKind::Program(stmts), loc)
+    }
+
+    fn error(loc: Loc) -> Self {
+        Self::new(AstKind::Error, loc)
+    }
 }
 
 macro_rules! operator_factory {
@@ -53,11 +170,22 @@ macro_rules! operator_factory {
 pub enum UniOperatorKind {
     Plus,
     Minus,
+    Not,
 }
 pub type UniOperator = Annotation<UniOperatorKind>;
 impl UniOperator {
     operator_factory!(plus, UniOperatorKind::Plus);
     operator_factory!(minus, UniOperatorKind::Minus);
+    operator_factory!(not, UniOperatorKind::Not);
+}
+impl std::fmt::Display for UniOperatorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UniOperatorKind::Plus => "+",
+            UniOperatorKind::Minus => "-",
+            UniOperatorKind::Not => "!",
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -66,6 +194,21 @@ pub enum BinOperatorKind {
     Sub,
     Mul,
     Div,
+    /// floored modulo, the remainder has the sign of the divisor
+    Mod,
+    Pow,
+    /// division rounded toward negative infinity
+    FloorDiv,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// short-circuit
+    And,
+    /// short-circuit
+    Or,
 }
 pub type BinOperator = Annotation<BinOperatorKind>;
 impl BinOperator {
@@ -73,15 +216,54 @@ impl BinOperator {
     operator_factory!(sub, BinOperatorKind::Sub);
     operator_factory!(mul, BinOperatorKind::Mul);
     operator_factory!(div, BinOperatorKind::Div);
+    operator_factory!(modulo, BinOperatorKind::Mod);
+    operator_factory!(pow, BinOperatorKind::Pow);
+    operator_factory!(floor_div, BinOperatorKind::FloorDiv);
+    operator_factory!(eq, BinOperatorKind::Eq);
+    operator_factory!(ne, BinOperatorKind::Ne);
+    operator_factory!(lt, BinOperatorKind::Lt);
+    operator_factory!(le, BinOperatorKind::Le);
+    operator_factory!(gt, BinOperatorKind::Gt);
+    operator_factory!(ge, BinOperatorKind::Ge);
+    operator_factory!(and, BinOperatorKind::And);
+    operator_factory!(or, BinOperatorKind::Or);
+}
+impl std::fmt::Display for BinOperatorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BinOperatorKind::Add => "+",
+            BinOperatorKind::Sub => "-",
+            BinOperatorKind::Mul => "*",
+            BinOperatorKind::Div => "/",
+            BinOperatorKind::Mod => "%",
+            BinOperatorKind::Pow => "^",
+            BinOperatorKind::FloorDiv => "//",
+            BinOperatorKind::Eq => "==",
+            BinOperatorKind::Ne => "!=",
+            BinOperatorKind::Lt => "<",
+            BinOperatorKind::Le => "<=",
+            BinOperatorKind::Gt => ">",
+            BinOperatorKind::Ge => ">=",
+            BinOperatorKind::And => "&&",
+            BinOperatorKind::Or => "||",
+        })
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParseError {
     UnexpectedToken(Token),
     NotExpression(Token),
     NotOperator(Token),
-    UnclosedOpenParen(Token),
+    /// the open paren and where the close paren was expected
+    UnclosedOpenParen(Token, Loc),
     RedundantExpression(Token),
+    /// the token opening a construct nested deeper than `ParseOptions::max_depth`
+    NestingTooDeep(Token),
+    /// the operator or paren that would make a chain such as `1 + 2 + 3` or `f(1)(2)` sink
+    /// deeper in the tree than `ParseOptions::max_tree_depth`
+    TreeTooDeep(Token),
     EOF,
 }
 impl ParseError {
@@ -90,105 +272,525 @@ impl ParseError {
             ParseError::UnexpectedToken(token) => Some(token),
             ParseError::NotExpression(token) => Some(token),
             ParseError::NotOperator(token) => Some(token),
-            ParseError::UnclosedOpenParen(token) => Some(token),
+            ParseError::UnclosedOpenParen(token, _) => Some(token),
             ParseError::RedundantExpression(token) => Some(token),
+            ParseError::NestingTooDeep(token) => Some(token),
+            ParseError::TreeTooDeep(token) => Some(token),
             ParseError::EOF => None,
         }
     }
 }
 
-pub struct Tokens(Vec<Token>);
+/// Parser state. Errors are recorded rather than returned so that a single pass reports
+/// all of them; the erroneous part of the input becomes an `AstKind::Error` node.
+pub struct Tokens<T: Iterator<Item = Token>> {
+    tokens: Peekable<T>,
+    /// location of the last consumed token
+    last_loc: Loc,
+    /// tokens closing the constructs being parsed, where error recovery stops skipping
+    closers: Vec<TokenKind>,
+    errors: Vec<ParseError>,
+    /// how many parens, operators and other constructs enclose the current position
+    depth: usize,
+    max_depth: usize,
+    /// how deep in the tree the node being parsed sits
+    tree_depth: usize,
+    /// how deep the deepest node of the innermost chain parsed so far sits
+    deepest: usize,
+    max_tree_depth: usize,
+    /// set once nesting gets too deep: the rest of the input is skipped and not reported on
+    abandoned: bool,
+}
 
 pub type ParseResult = Result<Ast, ParseError>;
 
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// deepest nesting of parens, unary operators, `^`, `if` and `fn` accepted, which bounds
+    /// the recursion of the parser
+    pub max_depth: usize,
+    /// deepest tree accepted, up to the `max_depth` levels nested last. Each operator of a
+    /// left-associative chain such as `1 + 2 + 3` or `f(1)(2)` puts the chain before it a
+    /// level deeper, so this bounds the length of chains as well as the recursion of
+    /// everything that walks the tree afterwards.
+    pub max_tree_depth: usize,
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        // about a megabyte of stack in a debug build for the parser, and a few for walking the
+        // tree in the interpreter
+        Self {
+            max_depth: 64,
+            max_tree_depth: 1000,
+        }
+    }
+}
+
+/// Parses `tokens`, failing on the first error.
 pub fn parse(tokens: Vec<Token>) -> ParseResult {
-    Tokens::parse(tokens)
+    parse_with(tokens, &ParseOptions::default())
+}
+
+pub fn parse_with(tokens: Vec<Token>, options: &ParseOptions) -> ParseResult {
+    let (ast, mut errors) = parse_recovering_with(tokens, options);
+    if errors.is_empty() {
+        Ok(ast)
+    } else {
+        Err(errors.remove(0))
+    }
+}
+
+/// Parses `tokens` to the end, returning a partial tree along with every error found.
+pub fn parse_recovering(tokens: Vec<Token>) -> (Ast, Vec<ParseError>) {
+    parse_recovering_with(tokens, &ParseOptions::default())
 }
 
-impl Tokens {
-    fn parse(tokens: Vec<Token>) -> ParseResult {
-        let mut tokens = tokens.into_iter().peekable();
-        let result = Self::parse_expr(&mut tokens);
-        match tokens.next() {
-            Some(token) => Err(ParseError::RedundantExpression(token)),
-            None => result,
+pub fn parse_recovering_with(tokens: Vec<Token>, options: &ParseOptions) -> (Ast, Vec<ParseError>) {
+    let mut parser = Tokens {
+        tokens: join_lines(tokens).into_iter().peekable(),
+        last_loc: Loc::new(0, 0),
+        closers: Vec::new(),
+        errors: Vec::new(),
+        depth: 0,
+        max_depth: options.max_depth,
+        tree_depth: 0,
+        deepest: 0,
+        max_tree_depth: options.max_tree_depth,
+        abandoned: false,
+    };
+    let ast = parser.parse();
+    (ast, parser.errors)
+}
+
+/// Drops the line breaks that do not end a statement: those inside parens, after a token
+/// that needs a continuation such as an operator, before `then`/`else`, and blank lines.
+fn join_lines(tokens: Vec<Token>) -> Vec<Token> {
+    let mut joined: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut depth = 0usize;
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token.value {
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => depth = depth.saturating_sub(1),
+            TokenKind::Newline => {
+                let ends_statement = joined.last().is_some_and(|last| {
+                    matches!(
+                        last.value,
+                        TokenKind::Number(_)
+                            | TokenKind::BigNumber(_)
+                            | TokenKind::Float(_)
+                            | TokenKind::Ident(_)
+                            | TokenKind::True
+                            | TokenKind::False
+                            | TokenKind::RParen
+                    )
+                });
+                let continued = matches!(
+                    tokens.peek().map(|next| &next.value),
+                    None | Some(TokenKind::Then) | Some(TokenKind::Else) | Some(TokenKind::Newline)
+                );
+                if depth > 0 || !ends_statement || continued {
+                    continue;
+                }
+            }
+            _ => {}
         }
+        joined.push(token);
     }
+    joined
+}
 
-    // expr = add;
-    fn parse_expr<T>(tokens: &mut Peekable<T>) -> ParseResult
-    where
-        T: Iterator<Item = Token>,
-    {
-        Self::parse_add(tokens)
+fn starts_expression(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Number(_)
+            | TokenKind::BigNumber(_)
+            | TokenKind::Float(_)
+            | TokenKind::Ident(_)
+            | TokenKind::Fn
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::If
+            | TokenKind::Bang
+            | TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::LParen
+    )
+}
+
+fn is_binary_operator(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Asterisk
+            | TokenKind::Slash
+            | TokenKind::Percent
+            | TokenKind::Caret
+            | TokenKind::DoubleAsterisk
+            | TokenKind::DoubleSlash
+            | TokenKind::EqualEqual
+            | TokenKind::NotEqual
+            | TokenKind::Less
+            | TokenKind::LessEqual
+            | TokenKind::Greater
+            | TokenKind::GreaterEqual
+            | TokenKind::AndAnd
+            | TokenKind::OrOr
+    )
+}
+
+impl<T: Iterator<Item = Token>> Tokens<T> {
+    // program = sep* (stmt (sep+ stmt)*)? sep* ;
+    // sep = ";" | newline ;
+    fn parse(&mut self) -> Ast {
+        let separators = [TokenKind::Semicolon, TokenKind::Newline];
+        let mut stmts = self.with_closers(&separators, |parser| {
+            let mut stmts = Vec::new();
+            loop {
+                while parser.expect(TokenKind::Semicolon).is_ok()
+                    || parser.expect(TokenKind::Newline).is_ok()
+                {}
+                if parser.peek().is_none() {
+                    return stmts;
+                }
+                parser.tree_depth = 0;
+                parser.deepest = 0;
+                let stmt = parser.parse_stmt().unwrap_or_else(|e| {
+                    parser.record(e);
+                    Ast::error(parser.last_loc.clone())
+                });
+                parser.parse_rest();
+                stmts.push(stmt);
+            }
+        });
+        match stmts.len() {
+            0 => {
+                self.errors.push(ParseError::EOF);
+                Ast::error(self.last_loc.clone())
+            }
+            1 => stmts.remove(0),
+            _ => {
+                let location = stmts[0].loc.merge(&stmts[stmts.len() - 1].loc);
+                Ast::program(stmts, location)
+            }
+        }
     }
 
-    // add = mul ("+" mul | "-" mul)* ;
-    fn parse_add<T>(tokens: &mut Peekable<T>) -> ParseResult
-    where
-        T: Iterator<Item = Token>,
-    {
-        let mut ast = Self::parse_mul(tokens)?;
-        loop {
-            match tokens.peek().map(|token| &token.value) {
-                Some(TokenKind::Plus) | Some(TokenKind::Minus) => {
-                    let operator = match tokens.next().unwrap() {
-                        Token {
-                            value: TokenKind::Plus,
-                            loc,
-                        } => BinOperator::add(loc),
-                        Token {
-                            value: TokenKind::Minus,
-                            loc,
-                        } => BinOperator::sub(loc),
-                        _ => unreachable!(),
-                    };
-                    let rhs = Self::parse_mul(tokens)?;
-                    let location = ast.loc.merge(&rhs.loc);
-                    ast = Ast::bin_operator(operator, ast, rhs, location);
+    fn peek(&mut self) -> Option<&TokenKind> {
+        self.tokens.peek().map(|token| &token.value)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.next();
+        if let Some(token) = &token {
+            self.last_loc = token.loc.clone();
+        }
+        token
+    }
+
+    // where the next token starts, or the end of the input
+    fn position(&mut self) -> usize {
+        let end = self.last_loc.end;
+        self.tokens.peek().map_or(end, |token| token.loc.start)
+    }
+
+    // right after the last consumed token, where a missing token belongs
+    fn expected_loc(&self) -> Loc {
+        Loc::new(self.last_loc.end, self.last_loc.end)
+    }
+
+    fn with_closers<R>(&mut self, closers: &[TokenKind], f: impl FnOnce(&mut Self) -> R) -> R {
+        let depth = self.closers.len();
+        self.closers.extend_from_slice(closers);
+        let result = f(self);
+        self.closers.truncate(depth);
+        result
+    }
+
+    fn record(&mut self, error: ParseError) {
+        if !self.abandoned {
+            self.errors.push(error);
+        }
+    }
+
+    /// Parses a construct opened by `opener` one level deeper, giving up on the whole input
+    /// when that exceeds the maximum depth.
+    fn nested<R>(
+        &mut self,
+        opener: &Token,
+        f: impl FnOnce(&mut Self) -> Result<R, ParseError>,
+    ) -> Result<R, ParseError> {
+        if self.depth == self.max_depth {
+            return Err(self.abandon(ParseError::NestingTooDeep(opener.clone())));
+        }
+        self.depth += 1;
+        self.tree_depth += 1;
+        self.deepest = std::cmp::max(self.deepest, self.tree_depth);
+        let result = f(self);
+        self.tree_depth -= 1;
+        self.depth -= 1;
+        result
+    }
+
+    /// Puts the chain parsed so far a level deeper under the node `operator` starts, giving up
+    /// on the whole input when that exceeds the maximum depth of the tree.
+    fn link(&mut self, operator: &Token) -> Result<(), ParseError> {
+        if self.deepest >= self.max_tree_depth {
+            return Err(self.abandon(ParseError::TreeTooDeep(operator.clone())));
+        }
+        self.deepest += 1;
+        Ok(())
+    }
+
+    // records `error` and skips the rest of the input, which is not reported on
+    fn abandon(&mut self, error: ParseError) -> ParseError {
+        self.record(error.clone());
+        self.abandoned = true;
+        while self.next().is_some() {}
+        error
+    }
+
+    /// Parses a left-associative chain of `operand`s joined by the tokens `operator` turns
+    /// into a binary operator.
+    fn parse_chain(
+        &mut self,
+        operand: fn(&mut Self) -> ParseResult,
+        operator: fn(&Token) -> Option<BinOperator>,
+    ) -> ParseResult {
+        let deepest = std::mem::replace(&mut self.deepest, self.tree_depth);
+        let mut ast = operand(self)?;
+        while let Some(operator) = self.tokens.peek().and_then(operator) {
+            let token = self.next().unwrap();
+            self.link(&token)?;
+            self.tree_depth += 1;
+            let rhs = operand(self);
+            self.tree_depth -= 1;
+            let rhs = rhs?;
+            let location = ast.loc.merge(&rhs.loc);
+            ast = Ast::bin_operator(operator, ast, rhs, location);
+        }
+        self.deepest = std::cmp::max(deepest, self.deepest);
+        Ok(ast)
+    }
+
+    /// Records `error` and skips the tokens that belong to the broken construct,
+    /// stopping at a binary operator or a token closing an enclosing construct.
+    fn recover(&mut self, error: ParseError, start: usize) -> Ast {
+        self.record(error);
+        let mut depth = 0;
+        while let Some(token) = self.tokens.peek() {
+            let kind = &token.value;
+            if depth == 0 && (is_binary_operator(kind) || self.closers.contains(kind)) {
+                break;
+            }
+            match kind {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen if depth > 0 => depth -= 1,
+                _ => {}
+            }
+            self.next();
+        }
+        let end = std::cmp::max(start, self.last_loc.end);
+        Ast::error(Loc::new(start, end))
+    }
+
+    /// Reports the tokens that cannot continue the expression just parsed, up to a closer or
+    /// the end of the input. Whatever follows is parsed as well, so its errors are reported too.
+    fn parse_rest(&mut self) {
+        while let Some(token) = self.tokens.peek() {
+            if self.closers.contains(&token.value) {
+                return;
+            }
+            let token = token.clone();
+            if starts_expression(&token.value) {
+                self.errors.push(ParseError::NotOperator(token));
+                let _ = self.parse_expr();
+            } else {
+                self.errors.push(ParseError::RedundantExpression(token));
+                self.next();
+                // an operator after the redundant token continues it, e.g. `1) * 2`
+                if self.peek().is_some_and(is_binary_operator) {
+                    self.next();
+                    if self.peek().is_some_and(starts_expression) {
+                        let _ = self.parse_expr();
+                    }
                 }
-                _ => return Ok(ast),
             }
         }
     }
 
-    // mul = unary ("*" unary | "/" unary)* ;
-    fn parse_mul<T>(tokens: &mut Peekable<T>) -> ParseResult
-    where
-        T: Iterator<Item = Token>,
-    {
-        let mut ast = Self::parse_unary(tokens)?;
-        loop {
-            match tokens.peek().map(|token| &token.value) {
-                Some(TokenKind::Asterisk) | Some(TokenKind::Slash) => {
-                    let operator = match tokens.next().unwrap() {
-                        Token {
-                            value: TokenKind::Asterisk,
-                            loc,
-                        } => BinOperator::mul(loc),
-                        Token {
-                            value: TokenKind::Slash,
-                            loc,
-                        } => BinOperator::div(loc),
-                        _ => unreachable!(),
-                    };
-                    let rhs = Self::parse_unary(tokens)?;
-                    let location = ast.loc.merge(&rhs.loc);
-                    ast = Ast::bin_operator(operator, ast, rhs, location);
+    fn expect(&mut self, expected: TokenKind) -> Result<Token, ParseError> {
+        match self.tokens.peek() {
+            Some(token) if token.value == expected => Ok(self.next().unwrap()),
+            Some(token) => Err(ParseError::UnexpectedToken(token.clone())),
+            None => Err(ParseError::EOF),
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<Identifier, ParseError> {
+        match self.tokens.peek() {
+            Some(Token {
+                value: TokenKind::Ident(_),
+                ..
+            }) => match self.next() {
+                Some(Token {
+                    value: TokenKind::Ident(name),
+                    loc,
+                }) => Ok(Identifier::new(name, loc)),
+                _ => unreachable!(),
+            },
+            Some(token) => Err(ParseError::UnexpectedToken(token.clone())),
+            None => Err(ParseError::EOF),
+        }
+    }
+
+    // stmt = "let" ident "=" expr | "fn" ident function | expr ;
+    fn parse_stmt(&mut self) -> ParseResult {
+        match self.peek() {
+            Some(TokenKind::Let) => {
+                let let_token = self.next().unwrap();
+                let name = self.expect_ident().and_then(|name| {
+                    self.expect(TokenKind::Equal)?;
+                    Ok(name)
+                });
+                match name {
+                    Ok(name) => {
+                        let value = self.parse_expr()?;
+                        let location = let_token.loc.merge(&value.loc);
+                        Ok(Ast::let_(name, value, location))
+                    }
+                    Err(e) => {
+                        let start = let_token.loc.start;
+                        self.with_closers(&[TokenKind::Equal], |parser| parser.recover(e, start));
+                        if self.expect(TokenKind::Equal).is_ok() {
+                            self.parse_expr()?;
+                        }
+                        Ok(Ast::error(Loc::new(start, self.last_loc.end)))
+                    }
+                }
+            }
+            Some(TokenKind::Fn) => {
+                let fn_token = self.next().unwrap();
+                match self.peek() {
+                    Some(TokenKind::Ident(_)) => {
+                        let name = self.expect_ident()?;
+                        let (params, body) = self.parse_function()?;
+                        let location = fn_token.loc.merge(&body.loc);
+                        Ok(Ast::fn_def(name, params, body, location))
+                    }
+                    _ => {
+                        let (params, body) = self.parse_function()?;
+                        let location = fn_token.loc.merge(&body.loc);
+                        Ok(Ast::lambda(params, body, location))
+                    }
                 }
-                _ => return Ok(ast),
             }
+            _ => self.parse_expr(),
         }
     }
 
-    // unary = ("+" | "-")? atom ;
-    fn parse_unary<T>(tokens: &mut Peekable<T>) -> ParseResult
-    where
-        T: Iterator<Item = Token>,
-    {
-        match tokens.peek().map(|token| &token.value) {
-            Some(TokenKind::Plus) | Some(TokenKind::Minus) => {
-                let operator = match tokens.next().unwrap() {
+    // function = "(" (ident ("," ident)*)? ")" "=" expr ;
+    // a malformed parameter list keeps the parameters read so far
+    fn parse_function(&mut self) -> Result<(Vec<Identifier>, Ast), ParseError> {
+        let mut params = Vec::new();
+        let start = self.position();
+        let header = self.with_closers(&[TokenKind::Equal], |parser| {
+            parser.parse_params(&mut params)?;
+            parser.expect(TokenKind::Equal)
+        });
+        if let Err(e) = header {
+            self.with_closers(&[TokenKind::Equal], |parser| parser.recover(e, start));
+            self.expect(TokenKind::Equal)?;
+        }
+        let body = self.parse_expr()?;
+        Ok((params, body))
+    }
+
+    fn parse_params(&mut self, params: &mut Vec<Identifier>) -> Result<(), ParseError> {
+        self.expect(TokenKind::LParen)?;
+        if self.expect(TokenKind::RParen).is_ok() {
+            return Ok(());
+        }
+        loop {
+            params.push(self.expect_ident()?);
+            if self.expect(TokenKind::Comma).is_err() {
+                self.expect(TokenKind::RParen)?;
+                return Ok(());
+            }
+        }
+    }
+
+    // expr = or ;
+    fn parse_expr(&mut self) -> ParseResult {
+        self.parse_or()
+    }
+
+    // or = and ("||" and)* ;
+    fn parse_or(&mut self) -> ParseResult {
+        self.parse_chain(Self::parse_and, |token| match token.value {
+            TokenKind::OrOr => Some(BinOperator::or(token.loc.clone())),
+            _ => None,
+        })
+    }
+
+    // and = cmp ("&&" cmp)* ;
+    fn parse_and(&mut self) -> ParseResult {
+        self.parse_chain(Self::parse_cmp, |token| match token.value {
+            TokenKind::AndAnd => Some(BinOperator::and(token.loc.clone())),
+            _ => None,
+        })
+    }
+
+    // cmp = add (("==" | "!=" | "<" | "<=" | ">" | ">=") add)? ;
+    // comparisons do not chain, `1 < 2 < 3` is an error
+    fn parse_cmp(&mut self) -> ParseResult {
+        let ast = self.parse_add()?;
+        let operator = match self.tokens.peek() {
+            Some(Token { value, loc }) => match value {
+                TokenKind::EqualEqual => BinOperator::eq(loc.clone()),
+                TokenKind::NotEqual => BinOperator::ne(loc.clone()),
+                TokenKind::Less => BinOperator::lt(loc.clone()),
+                TokenKind::LessEqual => BinOperator::le(loc.clone()),
+                TokenKind::Greater => BinOperator::gt(loc.clone()),
+                TokenKind::GreaterEqual => BinOperator::ge(loc.clone()),
+                _ => return Ok(ast),
+            },
+            None => return Ok(ast),
+        };
+        self.next();
+        let rhs = self.parse_add()?;
+        let location = ast.loc.merge(&rhs.loc);
+        Ok(Ast::bin_operator(operator, ast, rhs, location))
+    }
+
+    // add = mul ("+" mul | "-" mul)* ;
+    fn parse_add(&mut self) -> ParseResult {
+        self.parse_chain(Self::parse_mul, |token| match token.value {
+            TokenKind::Plus => Some(BinOperator::add(token.loc.clone())),
+            TokenKind::Minus => Some(BinOperator::sub(token.loc.clone())),
+            _ => None,
+        })
+    }
+
+    // mul = unary ("*" unary | "/" unary | "//" unary | "%" unary)* ;
+    fn parse_mul(&mut self) -> ParseResult {
+        self.parse_chain(Self::parse_unary, |token| match token.value {
+            TokenKind::Asterisk => Some(BinOperator::mul(token.loc.clone())),
+            TokenKind::Slash => Some(BinOperator::div(token.loc.clone())),
+            TokenKind::DoubleSlash => Some(BinOperator::floor_div(token.loc.clone())),
+            TokenKind::Percent => Some(BinOperator::modulo(token.loc.clone())),
+            _ => None,
+        })
+    }
+
+    // unary = ("+" | "-" | "!") unary | power ;
+    fn parse_unary(&mut self) -> ParseResult {
+        match self.peek() {
+            Some(TokenKind::Plus) | Some(TokenKind::Minus) | Some(TokenKind::Bang) => {
+                let token = self.next().unwrap();
+                let ast = self.nested(&token, |parser| parser.parse_unary())?;
+                let operator = match token {
                     Token {
                         value: TokenKind::Plus,
                         loc,
@@ -197,37 +799,284 @@ impl Tokens {
                         value: TokenKind::Minus,
                         loc,
                     } => UniOperator::minus(loc),
+                    Token {
+                        value: TokenKind::Bang,
+                        loc,
+                    } => UniOperator::not(loc),
                     _ => unreachable!(),
                 };
-                let ast = Self::parse_atom(tokens)?;
-                let location = ast.loc.merge(&ast.loc);
+                let location = operator.loc.merge(&ast.loc);
                 Ok(Ast::uni_operator(operator, ast, location))
             }
-            _ => Self::parse_atom(tokens),
+            _ => self.parse_power(),
         }
     }
 
-    // atom = number | "(" add ")" ;
-    // number = digit+
-    // digit = "0" | ... | "9" ;
-    fn parse_atom<T>(tokens: &mut Peekable<T>) -> ParseResult
-    where
-        T: Iterator<Item = Token>,
-    {
-        tokens
-            .next()
-            .ok_or(ParseError::EOF)
-            .and_then(|token| match token.value {
-                TokenKind::Number(num) => Ok(Ast::number(num, token.loc)),
-                TokenKind::LParen => {
-                    let add = Self::parse_add(tokens)?;
-                    match tokens.next().map(|token| token.value) {
-                        Some(TokenKind::RParen) => Ok(add),
-                        Some(_) => Err(ParseError::RedundantExpression(token)),
-                        _ => Err(ParseError::UnclosedOpenParen(token)),
+    // power = call (("^" | "**") unary)? ;
+    // right-associative and binds tighter than a preceding unary minus: -2^2 = -(2^2)
+    fn parse_power(&mut self) -> ParseResult {
+        let ast = self.parse_call()?;
+        match self.peek() {
+            Some(TokenKind::Caret) | Some(TokenKind::DoubleAsterisk) => {
+                let token = self.next().unwrap();
+                let rhs = self.nested(&token, |parser| parser.parse_unary())?;
+                let operator = BinOperator::pow(token.loc);
+                let location = ast.loc.merge(&rhs.loc);
+                Ok(Ast::bin_operator(operator, ast, rhs, location))
+            }
+            _ => Ok(ast),
+        }
+    }
+
+    // call = atom ("(" (expr ("," expr)*)? ")")* ;
+    // literals are never callees, so `2 (3)` stays a parse error
+    fn parse_call(&mut self) -> ParseResult {
+        let start = self.position();
+        let (tree_depth, deepest) = (self.tree_depth, self.deepest);
+        match self.parse_call_suffixes() {
+            Ok(ast) => Ok(ast),
+            Err(e) => {
+                // the chains cut short by the error are left at whatever depth they reached
+                self.tree_depth = tree_depth;
+                self.deepest = deepest;
+                Ok(self.recover(e, start))
+            }
+        }
+    }
+
+    fn parse_call_suffixes(&mut self) -> ParseResult {
+        let deepest = std::mem::replace(&mut self.deepest, self.tree_depth);
+        let mut ast = self.parse_atom()?;
+        if let AstKind::Number(_) | AstKind::BigNumber(_) | AstKind::Float(_) | AstKind::Bool(_) =
+            ast.value
+        {
+            self.deepest = std::cmp::max(deepest, self.deepest);
+            return Ok(ast);
+        }
+        while let Some(TokenKind::LParen) = self.peek() {
+            let lparen = self.next().unwrap();
+            self.link(&lparen)?;
+            let mut args = Vec::new();
+            let rparen = self.nested(&lparen, |parser| {
+                parser.with_closers(&[TokenKind::Comma, TokenKind::RParen], |parser| {
+                    if let Ok(rparen) = parser.expect(TokenKind::RParen) {
+                        return Ok(rparen);
+                    }
+                    loop {
+                        args.push(parser.parse_expr()?);
+                        parser.parse_rest();
+                        match parser.peek() {
+                            Some(TokenKind::Comma) => {
+                                parser.next();
+                            }
+                            Some(TokenKind::RParen) => return Ok(parser.next().unwrap()),
+                            _ => {
+                                let expected = parser.expected_loc();
+                                return Err(ParseError::UnclosedOpenParen(
+                                    lparen.clone(),
+                                    expected,
+                                ));
+                            }
+                        }
                     }
+                })
+            })?;
+            let location = ast.loc.merge(&rparen.loc);
+            ast = Ast::call(ast, args, location);
+        }
+        self.deepest = std::cmp::max(deepest, self.deepest);
+        Ok(ast)
+    }
+
+    // atom = number | "true" | "false" | ident | "(" expr ")" | "fn" function
+    //      | "if" expr "then" expr "else" expr ;
+    // number = digit+ ("." digit+)? (("e" | "E") ("+" | "-")? digit+)? ;
+    // digit = "0" | ... | "9" ;
+    // ident = (alphabet | "_") (alphabet | digit | "_")* ;
+    fn parse_atom(&mut self) -> ParseResult {
+        // a token that cannot start an expression is left for the caller to recover at
+        let token = match self.tokens.peek() {
+            None => return Err(ParseError::EOF),
+            Some(token) if !starts_expression(&token.value) => {
+                return Err(match token.value {
+                    TokenKind::RParen
+                    | TokenKind::Equal
+                    | TokenKind::Comma
+                    | TokenKind::Let
+                    | TokenKind::Then
+                    | TokenKind::Else => ParseError::UnexpectedToken(token.clone()),
+                    _ => ParseError::NotExpression(token.clone()),
+                })
+            }
+            Some(_) => self.next().unwrap(),
+        };
+        match token.value {
+            TokenKind::Number(num) => Ok(Ast::number(num, token.loc)),
+            TokenKind::BigNumber(num) => Ok(Ast::big_number(num, token.loc)),
+            TokenKind::Float(num) => Ok(Ast::float(num, token.loc)),
+            TokenKind::True => Ok(Ast::bool(true, token.loc)),
+            TokenKind::False => Ok(Ast::bool(false, token.loc)),
+            TokenKind::Ident(name) => Ok(Ast::variable(name, token.loc)),
+            TokenKind::If => self.nested(&token, |parser| {
+                let cond = parser.with_closers(&[TokenKind::Then], |parser| {
+                    let cond = parser.parse_expr();
+                    parser.parse_rest();
+                    cond
+                })?;
+                parser.expect(TokenKind::Then)?;
+                let then_branch = parser.with_closers(&[TokenKind::Else], |parser| {
+                    let then_branch = parser.parse_expr();
+                    parser.parse_rest();
+                    then_branch
+                })?;
+                parser.expect(TokenKind::Else)?;
+                let else_branch = parser.parse_expr()?;
+                let location = token.loc.merge(&else_branch.loc);
+                Ok(Ast::if_(cond, then_branch, else_branch, location))
+            }),
+            TokenKind::Fn => {
+                let (params, body) = self.nested(&token, |parser| parser.parse_function())?;
+                let location = token.loc.merge(&body.loc);
+                Ok(Ast::lambda(params, body, location))
+            }
+            TokenKind::LParen => {
+                let expr = self.nested(&token, |parser| {
+                    parser.with_closers(&[TokenKind::RParen], |parser| {
+                        let expr = parser.parse_expr();
+                        parser.parse_rest();
+                        expr
+                    })
+                })?;
+                match self.expect(TokenKind::RParen) {
+                    Ok(_) => Ok(expr),
+                    Err(_) => Err(ParseError::UnclosedOpenParen(token, self.expected_loc())),
                 }
-                _ => Err(ParseError::UnexpectedToken(token)),
-            })
+            }
+            // `+`, `-` and `!` are taken by `parse_unary`
+            _ => Err(ParseError::NotExpression(token)),
+        }
     }
 }
+
+#[test]
+fn test_parse_function() {
+    let ident = |name: &str, start, end| Identifier::new(name.to_string(), Loc::new(start, end));
+    let variable = |name: &str, start, end| Ast::variable(name.to_string(), Loc::new(start, end));
+    assert_eq!(
+        "fn square(x) = x * x".parse(),
+        Ok(Ast::fn_def(
+            ident("square", 3, 9),
+            vec![ident("x", 10, 11)],
+            Ast::bin_operator(
+                BinOperator::mul(Loc::new(17, 18)),
+                variable("x", 15, 16),
+                variable("x", 19, 20),
+                Loc::new(15, 20)
+            ),
+            Loc::new(0, 20)
+        ))
+    );
+    assert_eq!(
+        "max(a, 2)()".parse(),
+        Ok(Ast::call(
+            Ast::call(
+                variable("max", 0, 3),
+                vec![variable("a", 4, 5), Ast::number(2, Loc::new(7, 8))],
+                Loc::new(0, 9)
+            ),
+            vec![],
+            Loc::new(0, 11)
+        ))
+    );
+}
+
+#[test]
+fn test_parse_recovering() {
+    use crate::token::lex;
+    let (ast, errors) = parse_recovering(lex("1 + * 2 + (3 4) - )").unwrap());
+    assert_eq!(
+        errors,
+        vec![
+            ParseError::NotExpression(Token::asterisk(Loc::new(4, 5))),
+            ParseError::NotOperator(Token::number(4, Loc::new(13, 14))),
+            ParseError::UnexpectedToken(Token::rparen(Loc::new(18, 19))),
+        ]
+    );
+    assert_eq!(ast.loc, Loc::new(0, 19));
+    let (_, errors) = parse_recovering(lex("f(1, 2").unwrap());
+    assert_eq!(
+        errors,
+        vec![ParseError::UnclosedOpenParen(
+            Token::lparen(Loc::new(1, 2)),
+            Loc::new(6, 6)
+        )]
+    );
+    assert_eq!(
+        parse(lex("1 + 2").unwrap()).map(|ast| ast.loc),
+        Ok(Loc::new(0, 5))
+    );
+}
+
+#[test]
+fn test_parse_program() {
+    use crate::token::lex;
+    let program = "let r = 2\n\nfn area(x) =\n  3 * (x +\n  1)\nif area(r) > 10\nthen 1 else 0; r";
+    let ast = parse(lex(program).unwrap()).unwrap();
+    match ast.value {
+        AstKind::Program(stmts) => assert_eq!(stmts.len(), 4),
+        _ => panic!("expected a program: {:?}", ast),
+    }
+    let (_, errors) = parse_recovering(lex("1 +\n2 3\n* 4").unwrap());
+    assert_eq!(
+        errors,
+        vec![
+            ParseError::NotOperator(Token::number(3, Loc::new(6, 7))),
+            ParseError::NotExpression(Token::asterisk(Loc::new(8, 9))),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_nesting_limit() {
+    use crate::token::lex;
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse(lex(&nested(64)).unwrap()).is_ok());
+    // far beyond what the native stack holds, reported once at the first paren too many
+    let (_, errors) = parse_recovering(lex(&nested(100_000)).unwrap());
+    assert_eq!(
+        errors,
+        vec![ParseError::NestingTooDeep(Token::lparen(Loc::new(64, 65)))]
+    );
+    let options = ParseOptions {
+        max_depth: 3,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with(lex("-(2 ^ x)").unwrap(), &options).is_ok());
+    assert_eq!(
+        parse_with(lex("-(2 ^ -x)").unwrap(), &options),
+        Err(ParseError::NestingTooDeep(Token::minus(Loc::new(6, 7))))
+    );
+
+    // a chain nests as deep as it is long, whether it is flat or its first operand is deep
+    let sum = |terms: usize| format!("1{}", " + 1".repeat(terms - 1));
+    assert!(parse(lex(&sum(1001)).unwrap()).is_ok());
+    let (_, errors) = parse_recovering(lex(&sum(100_000)).unwrap());
+    assert_eq!(
+        errors,
+        vec![ParseError::TreeTooDeep(Token::plus(Loc::new(4002, 4003)))]
+    );
+    let options = ParseOptions {
+        max_tree_depth: 4,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with(lex("1 * 2 * 3 * 4 + 5").unwrap(), &options).is_ok());
+    assert!(parse_with(lex("f(1)(2) * 3 - 4").unwrap(), &options).is_ok());
+    assert_eq!(
+        parse_with(lex("1 * 2 * 3 * 4 * 5 + 6").unwrap(), &options),
+        Err(ParseError::TreeTooDeep(Token::plus(Loc::new(18, 19))))
+    );
+    assert_eq!(
+        parse_with(lex("f(1)(2)(3)(4)(5)").unwrap(), &options),
+        Err(ParseError::TreeTooDeep(Token::lparen(Loc::new(13, 14))))
+    );
+}
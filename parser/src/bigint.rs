@@ -0,0 +1,373 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Arbitrary-precision signed integer.
+///
+/// The magnitude is stored as little-endian base 2^32 digits without trailing
+/// zeros, so that derived equality and hashing are structural.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn normalize(mut self) -> Self {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+        if self.digits.is_empty() {
+            self.negative = false;
+        }
+        self
+    }
+
+    // self = self * mul + add
+    fn mul_add_small(&mut self, mul: u32, add: u32) {
+        let mut carry = add as u64;
+        for digit in self.digits.iter_mut() {
+            let value = *digit as u64 * mul as u64 + carry;
+            *digit = value as u32;
+            carry = value >> 32;
+        }
+        if carry > 0 {
+            self.digits.push(carry as u32);
+        }
+    }
+
+    // self = self / div, returns the remainder
+    fn div_rem_small(&mut self, div: u32) -> u32 {
+        let mut rem = 0u64;
+        for digit in self.digits.iter_mut().rev() {
+            let value = (rem << 32) | *digit as u64;
+            *digit = (value / div as u64) as u32;
+            rem = value % div as u64;
+        }
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+        rem as u32
+    }
+}
+
+impl BigInt {
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// How many bits the magnitude takes, 0 for zero.
+    pub fn bits(&self) -> u64 {
+        match self.digits.last() {
+            Some(top) => self.digits.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        let magnitude = match self.digits.as_slice() {
+            [] => 0,
+            [low] => *low as u64,
+            [low, high] => (*high as u64) << 32 | *low as u64,
+            _ => return None,
+        };
+        if self.negative {
+            // `i64::MIN` has no positive counterpart
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .digits
+            .iter()
+            .rev()
+            .fold(0.0, |acc, digit| acc * 4_294_967_296.0 + *digit as f64);
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            negative: false,
+            digits: self.digits.clone(),
+        }
+    }
+
+    /// Quotient rounded toward zero and the remainder with the sign of `self`, as `/` and `%`
+    /// do for `i64`; `None` when `divisor` is zero.
+    pub fn div_rem(&self, divisor: &Self) -> Option<(Self, Self)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.digits, &divisor.digits);
+        Some((
+            Self::with_sign(self.negative != divisor.negative, quotient),
+            Self::with_sign(self.negative, remainder),
+        ))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> Self {
+        let mut base = self.clone();
+        let mut result = Self::from(1i64);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    fn with_sign(negative: bool, digits: Vec<u32>) -> Self {
+        Self { negative, digits }.normalize()
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(num: i64) -> Self {
+        let magnitude = num.unsigned_abs();
+        Self::with_sign(num < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(num: u64) -> Self {
+        Self::with_sign(false, vec![num as u32, (num >> 32) as u32])
+    }
+}
+
+impl std::ops::Neg for &BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::with_sign(!self.negative, self.digits.clone())
+    }
+}
+
+impl std::ops::Add for &BigInt {
+    type Output = BigInt;
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::with_sign(self.negative, add_magnitude(&self.digits, &other.digits));
+        }
+        // the sign of the larger magnitude wins
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => {
+                BigInt::with_sign(other.negative, sub_magnitude(&other.digits, &self.digits))
+            }
+            _ => BigInt::with_sign(self.negative, sub_magnitude(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl std::ops::Sub for &BigInt {
+    type Output = BigInt;
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl std::ops::Mul for &BigInt {
+    type Output = BigInt;
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::with_sign(
+            self.negative != other.negative,
+            mul_magnitude(&self.digits, &other.digits),
+        )
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.digits, &other.digits),
+            (true, true) => cmp_magnitude(&other.digits, &self.digits),
+        }
+    }
+}
+
+// magnitudes are little-endian digits without trailing zeros
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() < b.len() { (b, a) } else { (a, b) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, digit) in long.iter().enumerate() {
+        let value = *digit as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(value as u32);
+        carry = value >> 32;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+// `a - b` for `a >= b`
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut value = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if value < 0 {
+            value += 1 << 32;
+            borrow = 1;
+        }
+        difference.push(value as u32);
+    }
+    difference
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let value = *x as u64 * *y as u64 + product[i + j] as u64 + carry;
+            product[i + j] = value as u32;
+            carry = value >> 32;
+        }
+        product[i + b.len()] = carry as u32;
+    }
+    product
+}
+
+// binary long division, one bit of the quotient at a time
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = vec![];
+    for bit in (0..a.len() * 32).rev() {
+        // remainder = remainder * 2 + the next bit of `a`
+        let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+        for digit in remainder.iter_mut() {
+            let top = *digit >> 31;
+            *digit = *digit << 1 | carry;
+            carry = top;
+        }
+        if carry > 0 {
+            remainder.push(carry);
+        }
+        if cmp_magnitude(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, b);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quotient, remainder)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParseBigIntError;
+
+impl std::str::FromStr for BigInt {
+    type Err = ParseBigIntError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        if digits.is_empty() {
+            return Err(ParseBigIntError);
+        }
+        let mut result = BigInt {
+            negative,
+            digits: Vec::new(),
+        };
+        for byte in digits.bytes() {
+            if !byte.is_ascii_digit() {
+                return Err(ParseBigIntError);
+            }
+            result.mul_add_small(10, (byte - b'0') as u32);
+        }
+        Ok(result.normalize())
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off 9 decimal digits at a time, least significant chunk first
+        let mut magnitude = self.clone();
+        let mut chunks = Vec::new();
+        while !magnitude.is_zero() {
+            chunks.push(magnitude.div_rem_small(1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_bigint_parse_and_display() {
+    let roundtrip = |s: &str| s.parse::<BigInt>().unwrap().to_string();
+    assert_eq!(roundtrip("0"), "0");
+    assert_eq!(roundtrip("-000"), "0");
+    assert_eq!(roundtrip("18446744073709551616"), "18446744073709551616");
+    assert_eq!(
+        roundtrip("-99999999999999999999999000000000012"),
+        "-99999999999999999999999000000000012"
+    );
+    assert_eq!("12a".parse::<BigInt>(), Err(ParseBigIntError));
+}
+
+#[test]
+fn test_bigint_arithmetic() {
+    let big = |s: &str| s.parse::<BigInt>().unwrap();
+    let two_to_the_100 = big("1267650600228229401496703205376");
+    assert_eq!(BigInt::from(2i64).pow(100), two_to_the_100);
+    assert_eq!(two_to_the_100.bits(), 101);
+    assert_eq!(
+        (&two_to_the_100 * &two_to_the_100).to_string(),
+        "1606938044258990275541962092341162602522202993782792835301376"
+    );
+    assert_eq!(
+        &BigInt::from(3i64) - &two_to_the_100,
+        -&big("1267650600228229401496703205373")
+    );
+    assert_eq!(&big("-5") + &big("5"), BigInt::from(0i64));
+    assert_eq!(
+        big("-1267650600228229401496703205377").div_rem(&BigInt::from(1i64 << 20)),
+        Some((big("-1208925819614629174706176"), BigInt::from(-1i64)))
+    );
+    assert_eq!(BigInt::from(1i64).div_rem(&BigInt::from(0i64)), None);
+    assert!(big("-3") < BigInt::from(2i64));
+    assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+    assert_eq!(BigInt::from(u64::MAX).to_i64(), None);
+    assert_eq!(two_to_the_100.to_f64(), 2f64.powi(100));
+}
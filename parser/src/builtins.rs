@@ -0,0 +1,413 @@
+use std::convert::TryFrom;
+
+use crate::{
+    bigint::BigInt,
+    interpreter::{InterpreterErrorKind, NumberKind},
+    rational::Rational,
+    value::Type,
+    value::Value,
+};
+
+type BuiltinResult = Result<Value, InterpreterErrorKind>;
+
+/// A function provided by the interpreter, looked up by name when no binding shadows it.
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub min_arity: usize,
+    /// `None` for variadic functions
+    pub max_arity: Option<usize>,
+    pub function: fn(&[Value]) -> BuiltinResult,
+}
+
+impl Builtin {
+    pub fn check_arity(&self, found: usize) -> Result<(), InterpreterErrorKind> {
+        if found < self.min_arity {
+            Err(InterpreterErrorKind::ArityMismatch {
+                expected: self.min_arity,
+                found,
+            })
+        } else {
+            match self.max_arity {
+                Some(max_arity) if max_arity < found => Err(InterpreterErrorKind::ArityMismatch {
+                    expected: max_arity,
+                    found,
+                }),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+macro_rules! builtin {
+    ($name: expr, $min_arity: expr, $max_arity: expr, $function: expr) => {
+        Builtin {
+            name: $name,
+            min_arity: $min_arity,
+            max_arity: $max_arity,
+            function: $function,
+        }
+    };
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    builtin!("abs", 1, Some(1), abs),
+    builtin!("min", 1, None, min),
+    builtin!("max", 1, None, max),
+    builtin!("pow", 2, Some(2), |args| power(&args[0], &args[1])),
+    builtin!("sqrt", 1, Some(1), sqrt),
+    builtin!("exp", 1, Some(1), |args| float(number(&args[0])?.exp())),
+    builtin!("floor", 1, Some(1), |args| round_with(
+        &args[0],
+        f64::floor,
+        Rational::floor
+    )),
+    builtin!("ceil", 1, Some(1), |args| round_with(
+        &args[0],
+        f64::ceil,
+        Rational::ceil
+    )),
+    builtin!("round", 1, Some(1), |args| round_with(
+        &args[0],
+        f64::round,
+        Rational::round
+    )),
+    builtin!("gcd", 2, Some(2), gcd),
+    builtin!("lcm", 2, Some(2), lcm),
+    builtin!("sin", 1, Some(1), |args| float(number(&args[0])?.sin())),
+    builtin!("cos", 1, Some(1), |args| float(number(&args[0])?.cos())),
+    builtin!("tan", 1, Some(1), |args| float(number(&args[0])?.tan())),
+    builtin!("asin", 1, Some(1), |args| inverse_trig(
+        "asin",
+        &args[0],
+        f64::asin
+    )),
+    builtin!("acos", 1, Some(1), |args| inverse_trig(
+        "acos",
+        &args[0],
+        f64::acos
+    )),
+    builtin!("atan", 1, Some(1), |args| float(number(&args[0])?.atan())),
+    builtin!("ln", 1, Some(1), ln),
+    builtin!("log", 1, Some(2), log),
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+fn domain_error(name: &str, arg: &Value) -> InterpreterErrorKind {
+    InterpreterErrorKind::DomainError(format!("{} is undefined for {}", name, arg))
+}
+
+fn number(value: &Value) -> Result<f64, InterpreterErrorKind> {
+    value.as_f64().ok_or(InterpreterErrorKind::TypeMismatch {
+        expected: Type::Number,
+        found: value.type_of(),
+    })
+}
+
+fn integer(name: &str, value: &Value) -> Result<i64, InterpreterErrorKind> {
+    match value {
+        Value::Int(num) => Ok(*num),
+        Value::Rational(num) if num.is_integer() => Ok(num.numerator()),
+        Value::BigInt(num) => num
+            .to_i64()
+            .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int)),
+        Value::Float(_) | Value::Rational(_) => Err(domain_error(name, value)),
+        _ => Err(InterpreterErrorKind::TypeMismatch {
+            expected: Type::Number,
+            found: value.type_of(),
+        }),
+    }
+}
+
+fn float(num: f64) -> BuiltinResult {
+    if num == f64::INFINITY {
+        Err(InterpreterErrorKind::Overflow(NumberKind::Float))
+    } else if num == f64::NEG_INFINITY {
+        Err(InterpreterErrorKind::Underflow(NumberKind::Float))
+    } else {
+        Ok(Value::Float(num))
+    }
+}
+
+fn abs(args: &[Value]) -> BuiltinResult {
+    match &args[0] {
+        Value::Int(num) => num
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int)),
+        Value::Rational(num) if *num < Rational::from(0) => num.checked_neg().map(Value::Rational),
+        Value::Rational(_) => Ok(args[0].clone()),
+        Value::BigInt(num) => Ok(Value::BigInt(num.abs())),
+        value => float(number(value)?.abs()),
+    }
+}
+
+fn min(args: &[Value]) -> BuiltinResult {
+    select(args, |candidate, current| candidate < current)
+}
+
+fn max(args: &[Value]) -> BuiltinResult {
+    select(args, |candidate, current| candidate > current)
+}
+
+// keeps the argument itself, so `min(1, 2.5)` stays an `Int`
+fn select(args: &[Value], better: fn(f64, f64) -> bool) -> BuiltinResult {
+    let mut selected = &args[0];
+    let mut selected_num = number(selected)?;
+    for arg in &args[1..] {
+        let num = number(arg)?;
+        if better(num, selected_num) {
+            selected = arg;
+            selected_num = num;
+        }
+    }
+    Ok(selected.clone())
+}
+
+/// `base ^ exponent`, shared with the `^` operator.
+/// A negative integer exponent gives a `Float`, or an exact `Rational` when either side is one.
+/// A `BigInt` raised to a non-negative integer is exact.
+pub fn power(base: &Value, exponent: &Value) -> BuiltinResult {
+    match (base, exponent) {
+        (Value::Int(0), Value::Int(exponent)) if *exponent < 0 => {
+            Err(InterpreterErrorKind::DivisionByZero)
+        }
+        // the bases whose powers stay in range, even for exponents too large for `u32`
+        (Value::Int(base @ -1..=1), Value::Int(exponent)) if *exponent >= 0 => {
+            let exponent = if *exponent == 0 { 0 } else { 2 - exponent % 2 };
+            Ok(Value::Int(base.pow(exponent as u32)))
+        }
+        (Value::Int(base), Value::Int(exponent)) if *exponent >= 0 => u32::try_from(*exponent)
+            .ok()
+            .and_then(|exponent| base.checked_pow(exponent))
+            .map(Value::Int)
+            .ok_or(if *base < 0 && exponent % 2 == 1 {
+                InterpreterErrorKind::Underflow(NumberKind::Int)
+            } else {
+                InterpreterErrorKind::Overflow(NumberKind::Int)
+            }),
+        (base_value, exponent) => {
+            if let Some(result) = exact_power(base_value, exponent) {
+                return result;
+            }
+            if let Some(result) = big_power(base_value, exponent) {
+                return result;
+            }
+            let (base, exponent) = (number(base_value)?, number(exponent)?);
+            if base == 0.0 && exponent < 0.0 {
+                return Err(InterpreterErrorKind::DivisionByZero);
+            }
+            let result = base.powf(exponent);
+            if result.is_nan() {
+                Err(domain_error("pow", base_value))
+            } else {
+                float(result)
+            }
+        }
+    }
+}
+
+// `None` unless one side is a `Rational` and the exponent is an integer
+fn exact_power(base: &Value, exponent: &Value) -> Option<BuiltinResult> {
+    if !matches!(base, Value::Rational(_)) && !matches!(exponent, Value::Rational(_)) {
+        return None;
+    }
+    let (base, exponent) = (base.as_rational()?, exponent.as_rational()?);
+    if !exponent.is_integer() {
+        return None;
+    }
+    Some(base.checked_pow(exponent.numerator()).map(Value::Rational))
+}
+
+// the largest power `big_power` computes, about 80,000 decimal digits
+const MAX_POWER_BITS: u64 = 1 << 18;
+
+// `None` unless one side is a `BigInt` and the exponent is a non-negative integer
+fn big_power(base: &Value, exponent: &Value) -> Option<BuiltinResult> {
+    if !matches!(base, Value::BigInt(_)) && !matches!(exponent, Value::BigInt(_)) {
+        return None;
+    }
+    let (base, exponent) = (base.as_big_int()?, exponent.as_big_int()?);
+    if exponent.is_negative() {
+        return None;
+    }
+    let odd = exponent
+        .div_rem(&BigInt::from(2i64))
+        .is_some_and(|(_, rem)| !rem.is_zero());
+    // 0, 1 and -1 stay small whatever the exponent
+    if base.bits() <= 1 {
+        return Some(Ok(Value::BigInt(match (exponent.is_zero(), odd) {
+            (true, _) => BigInt::from(1i64),
+            (false, false) => base.abs(),
+            (false, true) => base,
+        })));
+    }
+    let result = exponent
+        .to_i64()
+        .and_then(|exponent| u32::try_from(exponent).ok())
+        .filter(|exponent| base.bits() * *exponent as u64 <= MAX_POWER_BITS)
+        .map(|exponent| Value::BigInt(base.pow(exponent)))
+        .ok_or(if base.is_negative() && odd {
+            InterpreterErrorKind::Underflow(NumberKind::BigInt)
+        } else {
+            InterpreterErrorKind::Overflow(NumberKind::BigInt)
+        });
+    Some(result)
+}
+
+fn sqrt(args: &[Value]) -> BuiltinResult {
+    let num = number(&args[0])?;
+    if num < 0.0 {
+        Err(domain_error("sqrt", &args[0]))
+    } else {
+        float(num.sqrt())
+    }
+}
+
+fn round_with(
+    value: &Value,
+    round: fn(f64) -> f64,
+    exact: fn(Rational) -> Rational,
+) -> BuiltinResult {
+    match value {
+        Value::Int(num) => Ok(Value::Int(*num)),
+        Value::Rational(num) => Ok(Value::Rational(exact(*num))),
+        Value::BigInt(_) => Ok(value.clone()),
+        value => float(round(number(value)?)),
+    }
+}
+
+fn gcd_of(mut a: i64, mut b: i64) -> Result<i64, InterpreterErrorKind> {
+    while b != 0 {
+        let rem = a % b;
+        a = b;
+        b = rem;
+    }
+    // |i64::MIN| is not representable
+    a.checked_abs()
+        .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int))
+}
+
+fn gcd(args: &[Value]) -> BuiltinResult {
+    let (a, b) = (integer("gcd", &args[0])?, integer("gcd", &args[1])?);
+    gcd_of(a, b).map(Value::Int)
+}
+
+fn lcm(args: &[Value]) -> BuiltinResult {
+    let (a, b) = (integer("lcm", &args[0])?, integer("lcm", &args[1])?);
+    if a == 0 || b == 0 {
+        return Ok(Value::Int(0));
+    }
+    (a / gcd_of(a, b)?)
+        .checked_mul(b)
+        .and_then(i64::checked_abs)
+        .map(Value::Int)
+        .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int))
+}
+
+fn inverse_trig(name: &str, value: &Value, function: fn(f64) -> f64) -> BuiltinResult {
+    let num = number(value)?;
+    if !(-1.0..=1.0).contains(&num) {
+        Err(domain_error(name, value))
+    } else {
+        float(function(num))
+    }
+}
+
+fn ln(args: &[Value]) -> BuiltinResult {
+    let num = number(&args[0])?;
+    if num <= 0.0 {
+        Err(domain_error("ln", &args[0]))
+    } else {
+        float(num.ln())
+    }
+}
+
+// log(x) is the common logarithm, log(x, base) uses the given base
+fn log(args: &[Value]) -> BuiltinResult {
+    let num = number(&args[0])?;
+    if num <= 0.0 {
+        return Err(domain_error("log", &args[0]));
+    }
+    match args.get(1) {
+        None => float(num.log10()),
+        Some(base_value) => {
+            let base = number(base_value)?;
+            if base <= 0.0 || base == 1.0 {
+                Err(InterpreterErrorKind::DomainError(format!(
+                    "log is undefined for base {}",
+                    base_value
+                )))
+            } else {
+                float(num.log(base))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_builtins() {
+    let call = |name: &str, args: &[Value]| {
+        let builtin = lookup(name).unwrap();
+        builtin.check_arity(args.len())?;
+        (builtin.function)(args)
+    };
+    assert_eq!(call("abs", &[Value::Int(-3)]), Ok(Value::Int(3)));
+    assert_eq!(
+        call("max", &[Value::Int(1), Value::Float(2.5), Value::Int(2)]),
+        Ok(Value::Float(2.5))
+    );
+    assert_eq!(
+        call("pow", &[Value::Int(2), Value::Int(10)]),
+        Ok(Value::Int(1024))
+    );
+    assert_eq!(
+        call("pow", &[Value::Int(2), Value::Int(-1)]),
+        Ok(Value::Float(0.5))
+    );
+    assert_eq!(
+        call("pow", &[Value::Int(2), Value::Int(64)]),
+        Err(InterpreterErrorKind::Overflow(NumberKind::Int))
+    );
+    let huge = Value::Int(5_000_000_000);
+    for (base, expected) in [(1, 1), (0, 0), (-1, 1)].iter() {
+        let power = call("pow", &[Value::Int(*base), huge.clone()]);
+        assert_eq!(power, Ok(Value::Int(*expected)), "{}", base);
+    }
+    assert_eq!(
+        call("pow", &[Value::Int(-1), Value::Int(5_000_000_001)]),
+        Ok(Value::Int(-1))
+    );
+    assert_eq!(
+        call("pow", &[Value::Int(0), Value::Int(0)]),
+        Ok(Value::Int(1))
+    );
+    assert_eq!(
+        call("gcd", &[Value::Int(12), Value::Int(-18)]),
+        Ok(Value::Int(6))
+    );
+    assert_eq!(
+        call("lcm", &[Value::Int(4), Value::Int(6)]),
+        Ok(Value::Int(12))
+    );
+    assert_eq!(
+        call("sqrt", &[Value::Int(-1)]),
+        Err(InterpreterErrorKind::DomainError(
+            "sqrt is undefined for -1".to_string()
+        ))
+    );
+    assert_eq!(
+        call("log", &[Value::Int(8), Value::Int(2)]),
+        Ok(Value::Float(3.0))
+    );
+    assert_eq!(
+        call("min", &[]),
+        Err(InterpreterErrorKind::ArityMismatch {
+            expected: 1,
+            found: 0
+        })
+    );
+}
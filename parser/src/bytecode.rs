@@ -0,0 +1,678 @@
+//! Compiles an `Ast` into a flat list of instructions for a stack machine, and runs them.
+//!
+//! Unlike `interpreter::eval`, neither compiling nor running recurses on the Rust stack, so
+//! long chains such as `1 + 1 + ... + 1` and deep recursion in user functions do not overflow
+//! it. The one exception is copying the body of a function definition into its `Prototype`,
+//! once per compilation; the functions made from it at run time share that copy. Every
+//! instruction keeps the location of the node it was compiled from, so runtime errors
+//! point at the same source as the interpreter's.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::{
+    ast::{Ast, AstKind, BinOperator, BinOperatorKind, UniOperator, UniOperatorKind},
+    bigint::BigInt,
+    builtins,
+    interpreter::{
+        Environment, EvalOptions, Interpreter, InterpreterError, InterpreterErrorKind, NumberMode,
+    },
+    rational::Rational,
+    token::Loc,
+    value::{Function, Type, Value},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// pushes `constants[index]`
+    Constant(usize),
+    /// pushes the value bound to `names[index]`
+    Load(usize),
+    /// like `Load`, but an unbound name is reported as an unknown function
+    LoadCallee(usize),
+    /// binds `names[index]` to the top of the stack, leaving it there
+    Store(usize),
+    /// pushes `functions[index]`, closed over the current environment
+    Function(usize),
+    UniOperator(UniOperatorKind),
+    BinOperator(BinOperatorKind),
+    /// fails unless the top of the stack is a boolean
+    CheckBool,
+    /// fails unless the top of the stack is a function
+    CheckCallable,
+    /// fails unless the function on top of the stack takes that many arguments
+    CheckArity(usize),
+    /// pops that many arguments and the function below them, and calls it
+    Call(usize),
+    Jump(usize),
+    /// pops a boolean and jumps if it is false
+    JumpIfFalse(usize),
+    /// pops a boolean and jumps if it is true
+    JumpIfTrue(usize),
+    Pop,
+    /// literals that do not fit in `i64` and parts that failed to parse
+    Fail(InterpreterErrorKind),
+}
+
+/// A function or lambda as written, before it closes over an environment.
+#[derive(Debug, Clone)]
+pub struct Prototype {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Rc<Ast>,
+}
+
+/// Instructions compiled from one tree, with the tables they refer to.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub instructions: Vec<Instruction>,
+    /// `locs[i]` is where `instructions[i]` comes from
+    pub locs: Vec<Loc>,
+    pub constants: Vec<Value>,
+    pub names: Vec<String>,
+    pub functions: Vec<Prototype>,
+}
+
+impl std::fmt::Display for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, (instruction, loc)) in self.instructions.iter().zip(&self.locs).enumerate() {
+            let operand = match instruction {
+                Instruction::Constant(i) => format!(" {:?}", self.constants[*i]),
+                Instruction::Load(i) | Instruction::LoadCallee(i) | Instruction::Store(i) => {
+                    format!(" {}", self.names[*i])
+                }
+                Instruction::Function(i) => {
+                    let function = &self.functions[*i];
+                    let name = function.name.as_deref().unwrap_or("");
+                    format!(" {}({})", name, function.params.join(", "))
+                }
+                Instruction::UniOperator(operator) => format!(" {}", operator),
+                Instruction::BinOperator(operator) => format!(" {}", operator),
+                Instruction::CheckArity(n)
+                | Instruction::Call(n)
+                | Instruction::Jump(n)
+                | Instruction::JumpIfFalse(n)
+                | Instruction::JumpIfTrue(n) => format!(" {}", n),
+                Instruction::Fail(kind) => format!(" {:?}", kind),
+                Instruction::CheckBool | Instruction::CheckCallable | Instruction::Pop => {
+                    String::new()
+                }
+            };
+            let name = format!("{:?}", instruction);
+            let name = name.split('(').next().unwrap_or_default();
+            writeln!(f, "{:4} {}{} @{}", index, name, operand, loc)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn compile(ast: &Ast) -> Chunk {
+    compile_with(ast, NumberMode::default())
+}
+
+/// `compile` with integer literals as constants of `numbers`.
+pub fn compile_with(ast: &Ast, numbers: NumberMode) -> Chunk {
+    let mut compiler = Compiler {
+        numbers,
+        ..Compiler::default()
+    };
+    compiler.compile(ast);
+    compiler.chunk
+}
+
+enum Task<'a> {
+    Compile(&'a Ast),
+    /// jump targets are label numbers until `Compiler::compile` resolves them
+    Emit(Instruction, Loc),
+    Label(usize),
+}
+
+#[derive(Default)]
+struct Compiler {
+    numbers: NumberMode,
+    chunk: Chunk,
+    labels: Vec<usize>,
+}
+
+impl Compiler {
+    // an explicit stack of pending work instead of recursion
+    fn compile(&mut self, ast: &Ast) {
+        let mut tasks = vec![Task::Compile(ast)];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Compile(ast) => {
+                    let mut next = self.tasks(ast);
+                    next.reverse();
+                    tasks.extend(next);
+                }
+                Task::Emit(instruction, loc) => {
+                    self.chunk.instructions.push(instruction);
+                    self.chunk.locs.push(loc);
+                }
+                Task::Label(label) => self.labels[label] = self.chunk.instructions.len(),
+            }
+        }
+        for instruction in self.chunk.instructions.iter_mut() {
+            match instruction {
+                Instruction::Jump(target)
+                | Instruction::JumpIfFalse(target)
+                | Instruction::JumpIfTrue(target) => *target = self.labels[*target],
+                _ => {}
+            }
+        }
+    }
+
+    // what compiling `ast` amounts to, in order
+    fn tasks<'a>(&mut self, ast: &'a Ast) -> Vec<Task<'a>> {
+        let emit = |instruction| Task::Emit(instruction, ast.loc.clone());
+        match &ast.value {
+            AstKind::Number(num) => match (i64::try_from(*num), self.numbers) {
+                (Ok(num), NumberMode::Machine) => vec![emit(self.constant(Value::Int(num)))],
+                (Ok(num), NumberMode::Rational) => {
+                    vec![emit(self.constant(Value::Rational(Rational::from(num))))]
+                }
+                (_, NumberMode::BigInt) => {
+                    vec![emit(self.constant(Value::BigInt(BigInt::from(*num))))]
+                }
+                (Err(_), _) => vec![emit(Instruction::Fail(
+                    InterpreterErrorKind::LiteralOutOfRange,
+                ))],
+            },
+            AstKind::BigNumber(num) if self.numbers == NumberMode::BigInt => {
+                vec![emit(self.constant(Value::BigInt(num.clone())))]
+            }
+            AstKind::BigNumber(_) => vec![emit(Instruction::Fail(
+                InterpreterErrorKind::LiteralOutOfRange,
+            ))],
+            AstKind::Error => vec![emit(Instruction::Fail(InterpreterErrorKind::SyntaxError))],
+            AstKind::Float(num) => vec![emit(self.constant(Value::Float(num.0)))],
+            AstKind::Bool(value) => vec![emit(self.constant(Value::Bool(*value)))],
+            AstKind::UniOperator { operator, tree } => {
+                match Interpreter::negated_literal(operator, tree, self.numbers) {
+                    Some(num) => vec![emit(self.constant(num))],
+                    None => vec![
+                        Task::Compile(tree),
+                        emit(Instruction::UniOperator(operator.value.clone())),
+                    ],
+                }
+            }
+            AstKind::BinOperator { operator, lhs, rhs }
+                if operator.value == BinOperatorKind::And
+                    || operator.value == BinOperatorKind::Or =>
+            {
+                // `&&` skips `rhs` when `lhs` is false, `||` when it is true
+                let is_or = operator.value == BinOperatorKind::Or;
+                let (short_circuit, end) = (self.label(), self.label());
+                let jump = if is_or {
+                    Instruction::JumpIfTrue(short_circuit)
+                } else {
+                    Instruction::JumpIfFalse(short_circuit)
+                };
+                vec![
+                    Task::Compile(lhs),
+                    Task::Emit(jump, lhs.loc.clone()),
+                    Task::Compile(rhs),
+                    Task::Emit(Instruction::CheckBool, rhs.loc.clone()),
+                    emit(Instruction::Jump(end)),
+                    Task::Label(short_circuit),
+                    emit(self.constant(Value::Bool(is_or))),
+                    Task::Label(end),
+                ]
+            }
+            AstKind::BinOperator { operator, lhs, rhs } => vec![
+                Task::Compile(lhs),
+                Task::Compile(rhs),
+                emit(Instruction::BinOperator(operator.value.clone())),
+            ],
+            AstKind::Variable(name) => vec![emit(Instruction::Load(self.name(name)))],
+            AstKind::Let { name, value } => vec![
+                Task::Compile(value),
+                emit(Instruction::Store(self.name(&name.value))),
+            ],
+            AstKind::FnDef { name, params, body } => {
+                let function = self.function(Some(&name.value), params, body);
+                vec![
+                    emit(function),
+                    emit(Instruction::Store(self.name(&name.value))),
+                ]
+            }
+            AstKind::Lambda { params, body } => vec![emit(self.function(None, params, body))],
+            AstKind::Call { callee, args } => {
+                let mut tasks = match &callee.value {
+                    AstKind::Variable(name) => vec![Task::Emit(
+                        Instruction::LoadCallee(self.name(name)),
+                        callee.loc.clone(),
+                    )],
+                    _ => vec![Task::Compile(callee)],
+                };
+                tasks.push(Task::Emit(Instruction::CheckCallable, callee.loc.clone()));
+                tasks.push(emit(Instruction::CheckArity(args.len())));
+                tasks.extend(args.iter().map(Task::Compile));
+                tasks.push(emit(Instruction::Call(args.len())));
+                tasks
+            }
+            AstKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                let (else_label, end) = (self.label(), self.label());
+                vec![
+                    Task::Compile(cond),
+                    Task::Emit(Instruction::JumpIfFalse(else_label), cond.loc.clone()),
+                    Task::Compile(then_branch),
+                    emit(Instruction::Jump(end)),
+                    Task::Label(else_label),
+                    Task::Compile(else_branch),
+                    Task::Label(end),
+                ]
+            }
+            AstKind::Program(stmts) => {
+                let (last, init) = stmts.split_last().expect("programs are never empty");
+                let mut tasks = vec![];
+                for stmt in init {
+                    tasks.push(Task::Compile(stmt));
+                    tasks.push(Task::Emit(Instruction::Pop, stmt.loc.clone()));
+                }
+                tasks.push(Task::Compile(last));
+                tasks
+            }
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> Instruction {
+        self.chunk.constants.push(value);
+        Instruction::Constant(self.chunk.constants.len() - 1)
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        match self.chunk.names.iter().position(|known| known == name) {
+            Some(index) => index,
+            None => {
+                self.chunk.names.push(name.to_string());
+                self.chunk.names.len() - 1
+            }
+        }
+    }
+
+    fn function(
+        &mut self,
+        name: Option<&str>,
+        params: &[crate::ast::Identifier],
+        body: &Ast,
+    ) -> Instruction {
+        self.chunk.functions.push(Prototype {
+            name: name.map(|name| name.to_string()),
+            params: params.iter().map(|param| param.value.clone()).collect(),
+            body: Rc::new(body.clone()),
+        });
+        Instruction::Function(self.chunk.functions.len() - 1)
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels.push(0);
+        self.labels.len() - 1
+    }
+}
+
+pub fn eval(ast: &Ast) -> Result<Value, InterpreterError> {
+    eval_with(ast, &mut Environment::new())
+}
+
+pub fn eval_with(ast: &Ast, env: &mut Environment) -> Result<Value, InterpreterError> {
+    run(Rc::new(compile(ast)), env)
+}
+
+/// Compiles `ast` in the number mode of `options` and runs it within their limits.
+pub fn eval_with_options(
+    ast: &Ast,
+    env: &mut Environment,
+    options: &EvalOptions,
+) -> Result<Value, InterpreterError> {
+    run_with_options(Rc::new(compile_with(ast, options.numbers)), env, options)
+}
+
+/// Runs `chunk` with the bindings of `env`, which keeps those made before an error, if any.
+pub fn run(chunk: Rc<Chunk>, env: &mut Environment) -> Result<Value, InterpreterError> {
+    run_with(chunk, env, NumberMode::default(), None)
+}
+
+// calls in progress, which live on the heap rather than the native stack, so that runaway
+// recursion stops before it takes all memory
+const MAX_FRAMES: usize = 1 << 20;
+
+/// Like `run`, with the fuel of `options` counting instructions. Their `max_depth` guards
+/// the native stack of the interpreter, which the frames of calls here do not use.
+/// Function bodies are compiled in the number mode of `options`, which should be the one
+/// `chunk` was compiled in.
+pub fn run_with_options(
+    chunk: Rc<Chunk>,
+    env: &mut Environment,
+    options: &EvalOptions,
+) -> Result<Value, InterpreterError> {
+    run_with(chunk, env, options.numbers, options.fuel)
+}
+
+fn run_with(
+    chunk: Rc<Chunk>,
+    env: &mut Environment,
+    numbers: NumberMode,
+    fuel: Option<u64>,
+) -> Result<Value, InterpreterError> {
+    let mut vm = Vm {
+        stack: vec![],
+        frames: vec![Frame {
+            chunk,
+            ip: 0,
+            env: std::mem::take(env),
+        }],
+        compiled: HashMap::new(),
+        numbers,
+        fuel,
+    };
+    let result = vm.run();
+    *env = std::mem::take(&mut vm.frames[0].env);
+    result
+}
+
+// a function call in progress; the first frame runs the top-level chunk
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    env: Environment,
+}
+
+struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    /// bodies of the functions called so far, compiled on their first call
+    compiled: CompiledFunctions,
+    numbers: NumberMode,
+    fuel: Option<u64>,
+}
+type CompiledFunctions = HashMap<*const Function, (Rc<Function>, Rc<Chunk>)>;
+
+impl Vm {
+    fn run(&mut self) -> Result<Value, InterpreterError> {
+        let Vm {
+            stack,
+            frames,
+            compiled,
+            numbers,
+            fuel,
+        } = self;
+        loop {
+            let frame = frames.last_mut().expect("the first frame is never popped");
+            let chunk = frame.chunk.clone();
+            let ip = frame.ip;
+            if ip == chunk.instructions.len() {
+                if frames.len() == 1 {
+                    return Ok(pop(stack));
+                }
+                frames.pop();
+                continue;
+            }
+            frame.ip += 1;
+            let error = |kind| InterpreterError::new(kind, chunk.locs[ip].clone());
+            match fuel {
+                Some(0) => return Err(error(InterpreterErrorKind::OutOfFuel)),
+                Some(fuel) => *fuel -= 1,
+                None => {}
+            }
+            match &chunk.instructions[ip] {
+                Instruction::Constant(index) => stack.push(chunk.constants[*index].clone()),
+                Instruction::Load(index) => {
+                    let name = &chunk.names[*index];
+                    let value = lookup(&frame.env, name).ok_or_else(|| {
+                        error(InterpreterErrorKind::UnboundVariable(name.clone()))
+                    })?;
+                    stack.push(value);
+                }
+                Instruction::LoadCallee(index) => {
+                    let name = &chunk.names[*index];
+                    let value = lookup(&frame.env, name).ok_or_else(|| {
+                        error(InterpreterErrorKind::UnknownFunction(name.clone()))
+                    })?;
+                    stack.push(value);
+                }
+                Instruction::Store(index) => {
+                    let value = stack.last().expect("stores follow a value").clone();
+                    frame.env.set(&chunk.names[*index], value);
+                }
+                Instruction::Function(index) => {
+                    let prototype = &chunk.functions[*index];
+                    stack.push(Value::Function(Rc::new(Function {
+                        name: prototype.name.clone(),
+                        params: prototype.params.clone(),
+                        body: prototype.body.clone(),
+                        env: frame.env.clone(),
+                    })));
+                }
+                Instruction::UniOperator(operator) => {
+                    let operator = UniOperator::new(operator.clone(), chunk.locs[ip].clone());
+                    let value = pop(stack);
+                    let value = Interpreter::eval_uni_operator(&operator, value).map_err(error)?;
+                    stack.push(value);
+                }
+                Instruction::BinOperator(operator) => {
+                    let operator = BinOperator::new(operator.clone(), chunk.locs[ip].clone());
+                    let right = pop(stack);
+                    let left = pop(stack);
+                    let value =
+                        Interpreter::eval_bin_operator(&operator, left, right).map_err(error)?;
+                    stack.push(value);
+                }
+                Instruction::CheckBool => match stack.last() {
+                    Some(Value::Bool(_)) => {}
+                    value => return Err(error(type_mismatch(Type::Bool, value))),
+                },
+                Instruction::CheckCallable => match stack.last() {
+                    Some(Value::Function(_)) | Some(Value::Builtin(_)) => {}
+                    value => return Err(error(type_mismatch(Type::Function, value))),
+                },
+                Instruction::CheckArity(found) => match stack.last() {
+                    Some(Value::Function(function)) if function.params.len() != *found => {
+                        return Err(error(InterpreterErrorKind::ArityMismatch {
+                            expected: function.params.len(),
+                            found: *found,
+                        }))
+                    }
+                    Some(Value::Builtin(builtin)) => builtin.check_arity(*found).map_err(error)?,
+                    _ => {}
+                },
+                Instruction::Call(argc) => {
+                    let args = stack.split_off(stack.len() - argc);
+                    match pop(stack) {
+                        Value::Builtin(builtin) => {
+                            let value = (builtin.function)(&args).map_err(error)?;
+                            stack.push(value);
+                        }
+                        Value::Function(function) => {
+                            let mut env = function.env.clone();
+                            if let Some(name) = &function.name {
+                                env.set(name, Value::Function(function.clone()));
+                            }
+                            for (param, arg) in function.params.iter().zip(args) {
+                                env.set(param, arg);
+                            }
+                            if frames.len() - 1 == MAX_FRAMES {
+                                return Err(error(InterpreterErrorKind::RecursionLimit));
+                            }
+                            let chunk = chunk_of(compiled, *numbers, function);
+                            frames.push(Frame { chunk, ip: 0, env });
+                        }
+                        value => unreachable!("calls are checked first, found {:?}", value),
+                    }
+                }
+                Instruction::Jump(target) => frame.ip = *target,
+                Instruction::JumpIfFalse(target) | Instruction::JumpIfTrue(target) => {
+                    let jump_if = matches!(chunk.instructions[ip], Instruction::JumpIfTrue(_));
+                    match stack.pop() {
+                        Some(Value::Bool(value)) if value == jump_if => frame.ip = *target,
+                        Some(Value::Bool(_)) => {}
+                        value => return Err(error(type_mismatch(Type::Bool, value.as_ref()))),
+                    }
+                }
+                Instruction::Pop => {
+                    pop(stack);
+                }
+                Instruction::Fail(kind) => return Err(error(kind.clone())),
+            }
+        }
+    }
+}
+
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("instructions never underflow the stack")
+}
+
+fn chunk_of(
+    compiled: &mut CompiledFunctions,
+    numbers: NumberMode,
+    function: Rc<Function>,
+) -> Rc<Chunk> {
+    // the entry holds on to the function so that its address is not reused
+    let (_, chunk) = compiled.entry(Rc::as_ptr(&function)).or_insert_with(|| {
+        let chunk = Rc::new(compile_with(&function.body, numbers));
+        (function.clone(), chunk)
+    });
+    chunk.clone()
+}
+
+// bindings shadow built-in functions
+fn lookup(env: &Environment, name: &str) -> Option<Value> {
+    env.get(name)
+        .cloned()
+        .or_else(|| builtins::lookup(name).map(Value::Builtin))
+}
+
+fn type_mismatch(expected: Type, found: Option<&Value>) -> InterpreterErrorKind {
+    InterpreterErrorKind::TypeMismatch {
+        expected,
+        found: found.expect("checks follow a value").type_of(),
+    }
+}
+
+#[test]
+fn test_bytecode() {
+    use crate::interpreter;
+
+    let chunk = compile(&"if x then 1 else -2".parse().unwrap());
+    assert_eq!(
+        chunk.to_string(),
+        "   0 Load x @3-4
+   1 JumpIfFalse 4 @3-4
+   2 Constant Int(1) @10-11
+   3 Jump 6 @0-19
+   4 Constant Int(2) @18-19
+   5 UniOperator - @17-19
+"
+    );
+
+    // same values and errors, at the same locations, as the interpreter
+    let sources = [
+        "1 + 2 * 3 - 4 / 2",
+        "2 ^ 10 // 3 % 7",
+        "7 / 2.0 == 3.5",
+        "!(1 < 2) || 3 >= 3 && true",
+        "false && 1",
+        "true || 1",
+        "true && 1",
+        "if 1 then 2 else 3",
+        "let x = 2; let y = x * x; x + y",
+        "fn fact(n) = if n <= 1 then 1 else n * fact(n - 1); fact(20)",
+        "let add = fn(x, y) = x + y; add(1, max(2, 3))",
+        "fn adder(n) = fn(x) = x + n; adder(1)(41)",
+        "let sqrt = fn(x) = x; sqrt(-1)",
+        "sqrt(-1)",
+        "max()",
+        "undefined(1)",
+        "1 + y",
+        "let one = 1; one(2)",
+        "fn f(x) = x; f(1, 2)",
+        "9223372036854775807 + 1",
+        "1 + 9223372036854775808",
+        "-true",
+        "1 / (2 - 2)",
+    ];
+    for source in sources.iter() {
+        let ast: Ast = source.parse().unwrap();
+        assert_eq!(eval(&ast), interpreter::eval(&ast), "{}", source);
+    }
+
+    // bindings outlive the run, even one that fails
+    let mut env = Environment::new();
+    let ast: Ast = "let x = 1; let y = x / 0".parse().unwrap();
+    assert!(eval_with(&ast, &mut env).is_err());
+    assert_eq!(env.get("x"), Some(&Value::Int(1)));
+}
+
+#[test]
+fn test_bytecode_deep_nesting() {
+    use crate::{
+        ast::{parse_with, ParseOptions},
+        token::lex,
+    };
+
+    // `interpreter::eval` overflows the stack of a test thread at half this depth
+    let source = format!("0{}", " + 1".repeat(10_000));
+    let options = ParseOptions {
+        max_tree_depth: usize::MAX,
+        ..ParseOptions::default()
+    };
+    let ast = parse_with(lex(&source).unwrap(), &options).unwrap();
+    assert_eq!(eval(&ast), Ok(Value::Int(10_000)));
+    let source = "fn count(n) = if n == 0 then 0 else 1 + count(n - 1); count(100000)";
+    assert_eq!(eval(&source.parse().unwrap()), Ok(Value::Int(100_000)));
+
+    let forever = Rc::new(compile(&"fn f(n) = f(n + 1); f(0)".parse().unwrap()));
+    let limited = |options: &EvalOptions| {
+        run_with_options(forever.clone(), &mut Environment::new(), options).map_err(|e| e.value)
+    };
+    let fuel = EvalOptions {
+        fuel: Some(10_000),
+        ..EvalOptions::default()
+    };
+    assert_eq!(limited(&fuel), Err(InterpreterErrorKind::OutOfFuel));
+    // the interpreter's limit on calls in progress does not apply
+    let count = "fn count(n) = if n == 0 then 0 else 1 + count(n - 1); count(1000)";
+    let options = EvalOptions::default();
+    assert!(options.max_depth < 1000);
+    let result = eval_with_options(&count.parse().unwrap(), &mut Environment::new(), &options);
+    assert_eq!(result, Ok(Value::Int(1000)));
+}
+
+// cargo test --release bench_bytecode -- --ignored --nocapture
+#[test]
+#[ignore]
+fn bench_bytecode() {
+    use crate::interpreter;
+    use std::time::Instant;
+
+    let cases = [
+        // about as long a chain as the parser accepts by default
+        ("arithmetic", format!("1{}", " + 2 * 3 - 4".repeat(499))),
+        (
+            "recursion",
+            "fn fib(n) = if n < 2 then n else fib(n - 1) + fib(n - 2); fib(20)".to_string(),
+        ),
+    ];
+    for (name, source) in cases.iter() {
+        let ast: Ast = source.parse().unwrap();
+        let time = |eval: &dyn Fn(&Ast) -> Result<Value, InterpreterError>| {
+            let start = Instant::now();
+            for _ in 0..20 {
+                eval(&ast).unwrap();
+            }
+            start.elapsed() / 20
+        };
+        let chunk = Rc::new(compile(&ast));
+        println!(
+            "{}: interpreter {:?}, bytecode {:?}, bytecode without compiling {:?}",
+            name,
+            time(&interpreter::eval),
+            time(&eval),
+            time(&|_| run(chunk.clone(), &mut Environment::new())),
+        );
+    }
+}
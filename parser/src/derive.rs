@@ -0,0 +1,361 @@
+//! Symbolic differentiation: `derive` writes the derivative of an expression with respect to a
+//! variable as a new tree, by the sum, product, quotient, power and chain rules.
+//!
+//! Other variables are constants. `if` is derived branch by branch, and calls of the built-in
+//! functions `sin`, `cos`, `tan`, `exp`, `ln` and `sqrt` by the chain rule. A derivative is
+//! meant as real arithmetic: it divides by negative powers, e.g. `x ^ -1`, which do not
+//! truncate when evaluated with integers as `/` would, and the terms that the rules make zero,
+//! such as the derivative of a constant factor, are left out as it is written rather than by
+//! `optimize`.
+//! A node of the derivative spans the source of the expression it comes from.
+use crate::{
+    ast::{Ast, AstKind, BinOperator, BinOperatorKind, UniOperator, UniOperatorKind},
+    interpreter::{EvalOptions, NumberMode},
+    optimize,
+    token::{Annotation, Loc},
+    visit::{walk_ast, Visitor},
+};
+
+/// The part of a tree that has no derivative rule, described as e.g. `operator %`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NoRule(pub String);
+pub type DeriveError = Annotation<NoRule>;
+
+impl std::fmt::Display for DeriveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} cannot be differentiated", self.loc, self.value.0)
+    }
+}
+
+/// `derive`, simplified by `optimize` with constants folded exactly.
+pub fn derivative(ast: &Ast, var: &str) -> Result<Ast, DeriveError> {
+    let options = EvalOptions {
+        numbers: NumberMode::Rational,
+        ..EvalOptions::default()
+    };
+    Ok(optimize::optimize_with(derive(ast, var)?, &options))
+}
+
+pub fn derive(ast: &Ast, var: &str) -> Result<Ast, DeriveError> {
+    let loc = &ast.loc;
+    let unsupported = |what: String| Err(no_rule(what, loc));
+    let d = |ast: &Ast| derive(ast, var);
+    let derived = match &ast.value {
+        AstKind::Number(_) | AstKind::BigNumber(_) | AstKind::Float(_) => number(0, loc),
+        AstKind::Variable(name) => number((name == var) as u64, loc),
+        AstKind::UniOperator { operator, tree } => match operator.value {
+            UniOperatorKind::Plus => d(tree)?,
+            UniOperatorKind::Minus => difference(number(0, loc), d(tree)?, loc),
+            UniOperatorKind::Not => return unsupported(format!("operator {}", operator.value)),
+        },
+        AstKind::BinOperator { operator, lhs, rhs } => {
+            derive_bin_operator(ast, &operator.value, lhs, rhs, var)?
+        }
+        AstKind::Call { callee, args } => derive_call(ast, callee, args, var)?,
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => Ast::new(
+            AstKind::If {
+                cond: cond.clone(),
+                then_branch: Box::new(d(then_branch)?),
+                else_branch: Box::new(d(else_branch)?),
+            },
+            loc.clone(),
+        ),
+        AstKind::Bool(b) => return unsupported(format!("boolean {}", b)),
+        AstKind::Let { .. } => return unsupported("let".to_string()),
+        AstKind::FnDef { .. } | AstKind::Lambda { .. } => {
+            return unsupported("function".to_string())
+        }
+        AstKind::Program(_) => return unsupported("sequence".to_string()),
+        AstKind::Error => return unsupported("syntax error".to_string()),
+    };
+    Ok(derived)
+}
+
+// the derivative of `f operator g`, which is `ast`. Each rule has its own function so that
+// the recursion through the common `+` and `-` keeps small stack frames in debug builds.
+fn derive_bin_operator(
+    ast: &Ast,
+    operator: &BinOperatorKind,
+    f: &Ast,
+    g: &Ast,
+    var: &str,
+) -> Result<Ast, DeriveError> {
+    use BinOperatorKind::*;
+    let loc = &ast.loc;
+    match operator {
+        Add => Ok(sum(derive(f, var)?, derive(g, var)?, loc)),
+        Sub => Ok(difference(derive(f, var)?, derive(g, var)?, loc)),
+        Mul => product_rule(f, g, var, loc),
+        Div => quotient_rule(f, g, var, loc),
+        Pow if !depends_on(g, var) => power_rule(f, g, var, loc),
+        Pow => exponent_rule(ast, f, g, var),
+        _ => Err(no_rule(format!("operator {}", operator), loc)),
+    }
+}
+
+// f'g + fg'
+fn product_rule(f: &Ast, g: &Ast, var: &str, loc: &Loc) -> Result<Ast, DeriveError> {
+    let (df, dg) = (derive(f, var)?, derive(g, var)?);
+    Ok(sum(
+        product(df, g.clone(), loc),
+        product(f.clone(), dg, loc),
+        loc,
+    ))
+}
+
+// (f'g - fg') g^-2
+fn quotient_rule(f: &Ast, g: &Ast, var: &str, loc: &Loc) -> Result<Ast, DeriveError> {
+    let (df, dg) = (derive(f, var)?, derive(g, var)?);
+    let numerator = difference(
+        product(df, g.clone(), loc),
+        product(f.clone(), dg, loc),
+        loc,
+    );
+    Ok(product(numerator, reciprocal(g.clone(), 2, loc), loc))
+}
+
+// g f^(g - 1) f' for an exponent g that does not depend on the variable
+fn power_rule(f: &Ast, g: &Ast, var: &str, loc: &Loc) -> Result<Ast, DeriveError> {
+    use BinOperatorKind::*;
+    let df = derive(f, var)?;
+    let power = match g.value {
+        AstKind::Number(2) => f.clone(),
+        AstKind::Number(n) if n > 2 => bin_operator(Pow, f.clone(), number(n - 1, loc), loc),
+        _ => bin_operator(
+            Pow,
+            f.clone(),
+            bin_operator(Sub, g.clone(), number(1, loc), loc),
+            loc,
+        ),
+    };
+    Ok(product(product(g.clone(), power, loc), df, loc))
+}
+
+// f^g (g' ln(f) + g f' f^-1), where `ast` is f^g
+fn exponent_rule(ast: &Ast, f: &Ast, g: &Ast, var: &str) -> Result<Ast, DeriveError> {
+    use BinOperatorKind::*;
+    let loc = &ast.loc;
+    let bin = |kind, lhs, rhs| bin_operator(kind, lhs, rhs, loc);
+    let (dg, df) = (derive(g, var)?, derive(f, var)?);
+    Ok(bin(
+        Mul,
+        ast.clone(),
+        sum(
+            product(dg, call("ln", f.clone(), loc), loc),
+            product(
+                product(g.clone(), df, loc),
+                reciprocal(f.clone(), 1, loc),
+                loc,
+            ),
+            loc,
+        ),
+    ))
+}
+
+// the derivative of `callee(args)`, which is `ast`
+fn derive_call(ast: &Ast, callee: &Ast, args: &[Ast], var: &str) -> Result<Ast, DeriveError> {
+    use BinOperatorKind::*;
+    let loc = &ast.loc;
+    let bin = |kind, lhs, rhs| bin_operator(kind, lhs, rhs, loc);
+    match (&callee.value, args) {
+        (AstKind::Variable(name), [arg]) => {
+            let outer = match name.as_str() {
+                "sin" => call("cos", arg.clone(), loc),
+                "cos" => negate(call("sin", arg.clone(), loc), loc),
+                "tan" => reciprocal(call("cos", arg.clone(), loc), 2, loc),
+                "exp" => ast.clone(),
+                "ln" => reciprocal(arg.clone(), 1, loc),
+                "sqrt" => reciprocal(bin(Mul, number(2, loc), ast.clone()), 1, loc),
+                _ => return Err(no_rule(format!("function {}", name), loc)),
+            };
+            Ok(product(outer, derive(arg, var)?, loc))
+        }
+        _ => Err(no_rule("function call".to_string(), loc)),
+    }
+}
+
+fn no_rule(what: String, loc: &Loc) -> DeriveError {
+    DeriveError::new(NoRule(what), loc.clone())
+}
+
+// whether `var` occurs in `ast`
+fn depends_on(ast: &Ast, var: &str) -> bool {
+    struct Occurs<'a> {
+        var: &'a str,
+        found: bool,
+    }
+    impl Visitor for Occurs<'_> {
+        fn visit_ast(&mut self, ast: &Ast) {
+            match &ast.value {
+                AstKind::Variable(name) if name == self.var => self.found = true,
+                _ => walk_ast(self, ast),
+            }
+        }
+    }
+    let mut occurs = Occurs { var, found: false };
+    occurs.visit_ast(ast);
+    occurs.found
+}
+
+fn number(num: u64, loc: &Loc) -> Ast {
+    Ast::new(AstKind::Number(num), loc.clone())
+}
+
+fn negate(tree: Ast, loc: &Loc) -> Ast {
+    Ast::new(
+        AstKind::UniOperator {
+            operator: UniOperator::new(UniOperatorKind::Minus, loc.clone()),
+            tree: Box::new(tree),
+        },
+        loc.clone(),
+    )
+}
+
+fn is_number(ast: &Ast, num: u64) -> bool {
+    ast.value == AstKind::Number(num)
+}
+
+// `lhs + rhs` without a zero term
+fn sum(lhs: Ast, rhs: Ast, loc: &Loc) -> Ast {
+    if is_number(&lhs, 0) {
+        rhs
+    } else if is_number(&rhs, 0) {
+        lhs
+    } else {
+        bin_operator(BinOperatorKind::Add, lhs, rhs, loc)
+    }
+}
+
+// `lhs - rhs` without a zero term
+fn difference(lhs: Ast, rhs: Ast, loc: &Loc) -> Ast {
+    if is_number(&rhs, 0) {
+        lhs
+    } else if is_number(&lhs, 0) {
+        negate(rhs, loc)
+    } else {
+        bin_operator(BinOperatorKind::Sub, lhs, rhs, loc)
+    }
+}
+
+// `lhs * rhs`, which is zero with a zero factor, without a factor of one or minus one
+fn product(lhs: Ast, rhs: Ast, loc: &Loc) -> Ast {
+    let is_minus_one = |ast: &Ast| match &ast.value {
+        AstKind::UniOperator { operator, tree } => {
+            operator.value == UniOperatorKind::Minus && is_number(tree, 1)
+        }
+        _ => false,
+    };
+    if is_number(&lhs, 0) || is_number(&rhs, 0) {
+        number(0, loc)
+    } else if is_number(&lhs, 1) {
+        rhs
+    } else if is_number(&rhs, 1) {
+        lhs
+    } else if is_minus_one(&lhs) {
+        negate(rhs, loc)
+    } else if is_minus_one(&rhs) {
+        negate(lhs, loc)
+    } else {
+        bin_operator(BinOperatorKind::Mul, lhs, rhs, loc)
+    }
+}
+
+// `tree ^ -exponent`, a fraction even for integers
+fn reciprocal(tree: Ast, exponent: u64, loc: &Loc) -> Ast {
+    let exponent = negate(number(exponent, loc), loc);
+    bin_operator(BinOperatorKind::Pow, tree, exponent, loc)
+}
+
+fn bin_operator(kind: BinOperatorKind, lhs: Ast, rhs: Ast, loc: &Loc) -> Ast {
+    Ast::new(
+        AstKind::BinOperator {
+            operator: BinOperator::new(kind, loc.clone()),
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+        loc.clone(),
+    )
+}
+
+fn call(name: &str, arg: Ast, loc: &Loc) -> Ast {
+    Ast::new(
+        AstKind::Call {
+            callee: Box::new(Ast::new(AstKind::Variable(name.to_string()), loc.clone())),
+            args: vec![arg],
+        },
+        loc.clone(),
+    )
+}
+
+#[test]
+fn test_derive() {
+    use crate::{
+        interpreter::{self, Environment},
+        value::Value,
+    };
+
+    let derived = |source: &str| {
+        derivative(&source.parse().unwrap(), "x")
+            .map(|ast| ast.to_string())
+            .map_err(|e| e.to_string())
+    };
+    let cases = [
+        ("x ^ 3", "x ^ 2 * 3"),
+        ("3 * x + 2", "3"),
+        ("x * y - y", "y"),
+        ("1 / x", "-x ^ -2"),
+        ("sin(x ^ 2)", "cos(x ^ 2) * (x * 2)"),
+        ("if x > 0 then x else -x", "if x > 0 then 1 else -1"),
+        ("y ^ 2", "0"),
+    ];
+    for (source, expected) in cases.iter() {
+        assert_eq!(derived(source), Ok(expected.to_string()), "{}", source);
+    }
+    // zero terms are left out by `derive` itself rather than by the optimizer
+    let unsimplified = derive(&"x ^ 2 * 3 - y".parse().unwrap(), "x").unwrap();
+    assert_eq!(unsimplified.to_string(), "2 * x * 3");
+    assert_eq!(
+        derived("x % 2"),
+        Err("0-5: operator % cannot be differentiated".to_string())
+    );
+    assert_eq!(
+        derived("1 + f(x)"),
+        Err("4-8: function f cannot be differentiated".to_string())
+    );
+
+    // integers divide exactly in a derivative evaluated with the default options
+    let cases = [
+        ("ln(x)", 2, 0.5),
+        ("1 / x", 2, -0.25),
+        ("x / (x + 2)", 2, 0.125),
+        ("sqrt(x)", 4, 0.25),
+        ("x ^ x", 2, 4.0 * (1.0 + 2f64.ln())),
+    ];
+    for (source, x, expected) in cases.iter() {
+        let ast = derivative(&source.parse().unwrap(), "x").unwrap();
+        let mut env = Environment::new();
+        env.set("x", Value::Int(*x));
+        let value = interpreter::eval_with(&ast, &mut env).unwrap();
+        assert_eq!(value.as_f64(), Some(*expected), "{}", source);
+    }
+
+    // the derivative against a central difference, in float arithmetic
+    let source = "x ^ x / sqrt(x) + exp(-x) * tan(x) - cos(ln(x))";
+    let ast: Ast = source.parse().unwrap();
+    let derivative = derivative(&ast, "x").unwrap();
+    let at = |ast: &Ast, x: f64| {
+        let mut env = Environment::new();
+        env.set("x", Value::Float(x));
+        interpreter::eval_with(ast, &mut env)
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    };
+    let (x, h) = (1.3, 1e-6);
+    let expected = (at(&ast, x + h) - at(&ast, x - h)) / (2.0 * h);
+    assert!((at(&derivative, x) - expected).abs() < 1e-6);
+}
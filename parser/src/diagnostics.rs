@@ -0,0 +1,364 @@
+//! Renders a `CompileError` against its source, in the style of
+//!
+//! ```text
+//! error[E0104]: unclosed `(`
+//!  --> <input>:1:1
+//!   |
+//! 1 | (1 + 2
+//!   | ^ this `(` is never closed
+//!   |       - expected `)` here
+//!   = help: add a `)`
+//! ```
+//!
+//! Error codes are stable: `E00xx` for the lexer, `E01xx` for the parser,
+//! `E02xx` for the interpreter and `E03xx` for the type checker.
+use crate::{
+    ast::ParseError,
+    builtins,
+    errors::CompileError,
+    interpreter::{InterpreterError, InterpreterErrorKind, NumberKind},
+    source_map::{Position, SourceMap},
+    token::{LexError, LexErrorKind, Loc},
+    typecheck::{TypeError, TypeErrorKind},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub loc: Loc,
+    pub message: String,
+    /// primary labels point at the error itself, secondary ones at related places
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn with_label(mut self, loc: Loc, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            loc,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, loc: Loc, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            loc,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Builds the diagnostic for `error`. `source` is needed to place errors
+    /// without a location, e.g. an unexpected end of input.
+    pub fn from_error(error: &CompileError, source: &str) -> Self {
+        match error {
+            CompileError::Lexer(e) => lex_diagnostic(e, source),
+            CompileError::Parser(e) => parse_diagnostic(e, source),
+            CompileError::Type(e) => type_diagnostic(e),
+            CompileError::Interpret(e) => interpreter_diagnostic(e),
+        }
+    }
+
+    /// Renders the diagnostic. `filename` defaults to `<input>`.
+    pub fn render(&self, source: &str, filename: Option<&str>) -> String {
+        let map = SourceMap::new(source);
+        let mut out = format!("error[{}]: {}\n", self.code, self.message);
+        let mut labels: Vec<(Position, &Label)> = self
+            .labels
+            .iter()
+            .map(|label| (map.position(label.loc.start), label))
+            .collect();
+        labels.sort_by_key(|(position, label)| (*position, !label.primary));
+        let width = labels
+            .iter()
+            .map(|(position, _)| (position.line + 1).to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+        let primary = labels.iter().find(|(_, label)| label.primary);
+        if let Some((position, _)) = primary.or_else(|| labels.first()) {
+            out += &format!(
+                "{} --> {}:{}\n",
+                &pad[1..],
+                filename.unwrap_or("<input>"),
+                position
+            );
+            out += &format!("{} |\n", pad);
+        }
+        let mut last_line = None;
+        for (position, label) in &labels {
+            if last_line != Some(position.line) {
+                last_line = Some(position.line);
+                let text = map.line(position.line);
+                out += &format!("{:>width$} | {}\n", position.line + 1, text, width = width)
+                    .replace(" \n", "\n");
+            }
+            let mark = if label.primary { "^" } else { "-" };
+            out += &format!(
+                "{} | {}{} {}\n",
+                pad,
+                " ".repeat(position.column),
+                mark.repeat(std::cmp::max(1, map.width(&label.loc))),
+                label.message
+            )
+            .replace(" \n", "\n");
+        }
+        for note in &self.notes {
+            out += &format!("{} = note: {}\n", pad, note);
+        }
+        if let Some(help) = &self.help {
+            out += &format!("{} = help: {}\n", pad, help);
+        }
+        out
+    }
+}
+
+// end of the input, where a missing token belongs
+fn end_of(source: &str) -> Loc {
+    let end = source.trim_end().len();
+    Loc::new(end, end)
+}
+
+fn lex_diagnostic(error: &LexError, source: &str) -> Diagnostic {
+    match error.value {
+        LexErrorKind::InvalidChar(c) => {
+            Diagnostic::new("E0001", format!("invalid character `{}`", c))
+                .with_label(error.loc.clone(), "not part of any token")
+        }
+        LexErrorKind::LiteralOutOfRange => {
+            Diagnostic::new("E0002", "numeric literal is out of range")
+                .with_label(error.loc.clone(), "does not fit in 64 bits")
+                .with_help("`:set bigint on` allows integers of any size")
+        }
+        LexErrorKind::FloatOutOfRange => {
+            Diagnostic::new("E0002", "numeric literal is out of range")
+                .with_label(error.loc.clone(), "is too large for a float")
+        }
+        LexErrorKind::EOF => Diagnostic::new("E0003", "unexpected end of input")
+            .with_label(end_of(source), "input ends here"),
+    }
+}
+
+fn parse_diagnostic(error: &ParseError, source: &str) -> Diagnostic {
+    match error {
+        ParseError::UnexpectedToken(token) => {
+            Diagnostic::new("E0101", format!("unexpected `{}`", token.value))
+                .with_label(token.loc.clone(), "not expected here")
+        }
+        ParseError::NotExpression(token) => Diagnostic::new(
+            "E0102",
+            format!("expected an expression, found `{}`", token.value),
+        )
+        .with_label(token.loc.clone(), "expected an expression"),
+        ParseError::NotOperator(token) => Diagnostic::new(
+            "E0103",
+            format!("expected an operator, found `{}`", token.value),
+        )
+        .with_label(token.loc.clone(), "expected an operator")
+        .with_help("insert an operator between the two expressions"),
+        ParseError::UnclosedOpenParen(token, expected) => {
+            Diagnostic::new("E0104", format!("unclosed `{}`", token.value))
+                .with_label(
+                    token.loc.clone(),
+                    format!("this `{}` is never closed", token.value),
+                )
+                .with_secondary_label(expected.clone(), "expected `)` here")
+                .with_help("add a `)`")
+        }
+        ParseError::RedundantExpression(token) => {
+            Diagnostic::new("E0105", format!("unexpected `{}`", token.value))
+                .with_label(token.loc.clone(), "nothing to close or continue here")
+                .with_help(format!("remove the `{}`", token.value))
+        }
+        ParseError::EOF => Diagnostic::new("E0106", "unexpected end of input")
+            .with_label(end_of(source), "expected an expression"),
+        ParseError::NestingTooDeep(token) => {
+            Diagnostic::new("E0107", "expression is nested too deeply")
+                .with_label(token.loc.clone(), "exceeds the nesting limit")
+                .with_note("the rest of the input is not checked")
+                .with_help("split the expression with `let`")
+        }
+        ParseError::TreeTooDeep(token) => Diagnostic::new("E0108", "expression is too long")
+            .with_label(token.loc.clone(), "exceeds the length limit")
+            .with_note("each operator of a chain such as `1 + 2 + 3` nests the ones before it")
+            .with_note("the rest of the input is not checked")
+            .with_help("split the expression with `let`"),
+    }
+}
+
+fn type_diagnostic(error: &TypeError) -> Diagnostic {
+    let loc = error.loc.clone();
+    match &error.value {
+        TypeErrorKind::Mismatch { expected, found } => Diagnostic::new("E0301", "mismatched types")
+            .with_label(loc, format!("expected {}, found {}", expected, found))
+            .with_note("types are checked before evaluation"),
+        TypeErrorKind::ArityMismatch { expected, found } => {
+            Diagnostic::new("E0302", "wrong number of arguments").with_label(
+                loc,
+                format!("expected {} argument(s) but {} given", expected, found),
+            )
+        }
+        TypeErrorKind::NestingTooDeep => {
+            Diagnostic::new("E0303", "expression is nested too deeply to check")
+                .with_label(loc, "exceeds the nesting limit")
+                .with_help("split the expression with `let`")
+        }
+    }
+}
+
+fn interpreter_diagnostic(error: &InterpreterError) -> Diagnostic {
+    let loc = error.loc.clone();
+    match &error.value {
+        InterpreterErrorKind::DivisionByZero => {
+            Diagnostic::new("E0201", "division by zero").with_label(loc, "divides by zero")
+        }
+        InterpreterErrorKind::Overflow(kind) => range_note(
+            Diagnostic::new("E0202", "arithmetic overflow").with_label(loc, "result is too large"),
+            *kind,
+        ),
+        InterpreterErrorKind::Underflow(kind) => range_note(
+            Diagnostic::new("E0203", "arithmetic underflow").with_label(loc, "result is too small"),
+            *kind,
+        ),
+        InterpreterErrorKind::LiteralOutOfRange => {
+            Diagnostic::new("E0204", "integer literal is out of range")
+                .with_label(loc, "does not fit in a 64-bit integer")
+        }
+        InterpreterErrorKind::UnboundVariable(name) => {
+            Diagnostic::new("E0205", format!("variable `{}` is not defined", name))
+                .with_label(loc, "not defined")
+                .with_help(format!("define it first with `let {} = ...`", name))
+        }
+        InterpreterErrorKind::UnknownFunction(name) => {
+            let names: Vec<&str> = builtins::BUILTINS.iter().map(|b| b.name).collect();
+            Diagnostic::new("E0206", format!("function `{}` is not defined", name))
+                .with_label(loc, "not defined")
+                .with_note(format!("built-in functions are {}", names.join(", ")))
+        }
+        InterpreterErrorKind::ArityMismatch { expected, found } => {
+            Diagnostic::new("E0207", "wrong number of arguments").with_label(
+                loc,
+                format!("expected {} argument(s) but {} given", expected, found),
+            )
+        }
+        InterpreterErrorKind::TypeMismatch { expected, found } => {
+            Diagnostic::new("E0208", "mismatched types")
+                .with_label(loc, format!("expected {}, found {}", expected, found))
+        }
+        InterpreterErrorKind::DomainError(message) => {
+            Diagnostic::new("E0209", message.clone()).with_label(loc, "argument out of domain")
+        }
+        InterpreterErrorKind::SyntaxError => Diagnostic::new("E0210", "syntax error")
+            .with_label(loc, "failed to parse")
+            .with_note("the expression cannot be evaluated until it parses"),
+        InterpreterErrorKind::OutOfFuel => Diagnostic::new("E0211", "step limit exceeded")
+            .with_label(loc, "evaluation stopped here")
+            .with_note("evaluation is limited to a number of steps"),
+        InterpreterErrorKind::RecursionLimit => Diagnostic::new("E0212", "recursion is too deep")
+            .with_label(loc, "one call too many in progress")
+            .with_help("check that recursive functions reach their base case"),
+    }
+}
+
+// notes which limit a result of `kind` is out of; that of floats goes without saying
+fn range_note(diagnostic: Diagnostic, kind: NumberKind) -> Diagnostic {
+    match kind {
+        NumberKind::Int => diagnostic.with_note("integers are 64 bits wide"),
+        NumberKind::BigInt => diagnostic.with_note("powers are limited to about 80,000 digits"),
+        NumberKind::Rational => {
+            diagnostic.with_note("fractions have 64-bit numerators and denominators")
+        }
+        NumberKind::Float => diagnostic,
+    }
+}
+
+#[test]
+fn test_render() {
+    use crate::token::Token;
+    let source = "(1 + 2";
+    let error = CompileError::Parser(ParseError::UnclosedOpenParen(
+        Token::lparen(Loc::new(0, 1)),
+        Loc::new(6, 6),
+    ));
+    assert_eq!(
+        Diagnostic::from_error(&error, source).render(source, Some("calc")),
+        "error[E0104]: unclosed `(`
+ --> calc:1:1
+  |
+1 | (1 + 2
+  | ^ this `(` is never closed
+  |       - expected `)` here
+  = help: add a `)`
+"
+    );
+    let error = CompileError::Parser(ParseError::EOF);
+    assert_eq!(
+        Diagnostic::from_error(&error, "").render("", None),
+        "error[E0106]: unexpected end of input
+ --> <input>:1:1
+  |
+1 |
+  | ^ expected an expression
+"
+    );
+    let source = "let x = 1\nlet é = 2\né $ x";
+    let error = CompileError::Lexer(LexError::invalid_char('$', Loc::new(24, 25)));
+    assert_eq!(
+        Diagnostic::from_error(&error, source).render(source, Some("main.calc")),
+        "error[E0001]: invalid character `$`
+ --> main.calc:3:3
+  |
+3 | é $ x
+  |   ^ not part of any token
+"
+    );
+}
+
+#[test]
+fn test_out_of_range() {
+    use crate::session::Session;
+    let diagnostic = |source: &str| {
+        let error = Session::new().run(source).unwrap_err();
+        Diagnostic::from_error(&error, source)
+    };
+    let integer = diagnostic("9223372036854775807 + 1");
+    assert_eq!(integer.notes, ["integers are 64 bits wide"]);
+    let float = diagnostic("1e308 * 10");
+    assert_eq!((float.code, float.notes.len()), ("E0202", 0));
+    let literal = diagnostic("1 + 99999999999999999999");
+    assert_eq!(literal.labels[0].message, "does not fit in 64 bits");
+    assert!(literal.help.is_some());
+    let literal = diagnostic("1 + 1e999");
+    assert_eq!(literal.labels[0].message, "is too large for a float");
+    assert_eq!(literal.help, None);
+}
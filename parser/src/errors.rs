@@ -1,13 +1,15 @@
 use crate::ast::ParseError;
 use crate::{
-    interpreter::InterpreterError,
+    interpreter::{InterpreterError, InterpreterErrorKind},
     token::{LexError, LexErrorKind},
+    typecheck::{TypeError, TypeErrorKind},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CompileError {
     Lexer(LexError),
     Parser(ParseError),
+    Type(TypeError),
     Interpret(InterpreterError),
 }
 
@@ -22,10 +24,39 @@ impl From<ParseError> for CompileError {
     }
 }
 
+impl From<TypeError> for CompileError {
+    fn from(e: TypeError) -> Self {
+        CompileError::Type(e)
+    }
+}
+
+impl From<InterpreterError> for CompileError {
+    fn from(e: InterpreterError) -> Self {
+        CompileError::Interpret(e)
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Lexer(e) => e.fmt(f),
+            CompileError::Parser(e) => e.fmt(f),
+            CompileError::Type(e) => e.fmt(f),
+            CompileError::Interpret(e) => e.fmt(f),
+        }
+    }
+}
+
 impl std::fmt::Display for LexError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.value {
             LexErrorKind::InvalidChar(char) => write!(f, "{}: invalid char '{}'", self.loc, char),
+            LexErrorKind::LiteralOutOfRange => {
+                write!(f, "{}: numeric literal is out of range", self.loc)
+            }
+            LexErrorKind::FloatOutOfRange => {
+                write!(f, "{}: float literal is out of range", self.loc)
+            }
             LexErrorKind::EOF => write!(f, "End of file"),
         }
     }
@@ -43,13 +74,76 @@ impl std::fmt::Display for ParseError {
             ParseError::NotOperator(token) => {
                 write!(f, "{}: {:?} is not an operator", token.loc, token.value)
             }
-            ParseError::UnclosedOpenParen(token) => {
+            ParseError::UnclosedOpenParen(token, _) => {
                 write!(f, "{}: {:?} is unclosed", token.loc, token.value)
             }
             ParseError::RedundantExpression(token) => {
                 write!(f, "{}: {:?} is redundant", token.loc, token.value)
             }
+            ParseError::NestingTooDeep(token) => {
+                write!(f, "{}: {:?} is nested too deeply", token.loc, token.value)
+            }
+            ParseError::TreeTooDeep(token) => {
+                write!(
+                    f,
+                    "{}: {:?} makes the expression too long",
+                    token.loc, token.value
+                )
+            }
             ParseError::EOF => write!(f, "End of file"),
         }
     }
 }
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            TypeErrorKind::Mismatch { expected, found } => {
+                write!(f, "{}: expected {} but found {}", self.loc, expected, found)
+            }
+            TypeErrorKind::ArityMismatch { expected, found } => write!(
+                f,
+                "{}: expected {} argument(s) but {} given",
+                self.loc, expected, found
+            ),
+            TypeErrorKind::NestingTooDeep => {
+                write!(f, "{}: expression is nested too deeply to check", self.loc)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            InterpreterErrorKind::DivisionByZero => write!(f, "{}: division by zero", self.loc),
+            InterpreterErrorKind::Overflow(_) => write!(f, "{}: arithmetic overflow", self.loc),
+            InterpreterErrorKind::Underflow(_) => write!(f, "{}: arithmetic underflow", self.loc),
+            InterpreterErrorKind::LiteralOutOfRange => {
+                write!(f, "{}: integer literal is out of range", self.loc)
+            }
+            InterpreterErrorKind::UnboundVariable(name) => {
+                write!(f, "{}: variable '{}' is not defined", self.loc, name)
+            }
+            InterpreterErrorKind::UnknownFunction(name) => {
+                write!(f, "{}: function '{}' is not defined", self.loc, name)
+            }
+            InterpreterErrorKind::ArityMismatch { expected, found } => write!(
+                f,
+                "{}: expected {} argument(s) but {} given",
+                self.loc, expected, found
+            ),
+            InterpreterErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "{}: expected {} but found {}", self.loc, expected, found)
+            }
+            InterpreterErrorKind::DomainError(message) => write!(f, "{}: {}", self.loc, message),
+            InterpreterErrorKind::SyntaxError => write!(f, "{}: syntax error", self.loc),
+            InterpreterErrorKind::OutOfFuel => {
+                write!(f, "{}: evaluation step limit exceeded", self.loc)
+            }
+            InterpreterErrorKind::RecursionLimit => {
+                write!(f, "{}: too many calls in progress", self.loc)
+            }
+        }
+    }
+}
@@ -1,65 +1,975 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
 use crate::{
-    ast::{Ast, AstKind, BinOperator, BinOperatorKind, UniOperator, UniOperatorKind},
+    ast::{Ast, AstKind, BinOperator, BinOperatorKind, Identifier, UniOperator, UniOperatorKind},
+    bigint::BigInt,
+    builtins,
+    rational::Rational,
     token::Annotation,
+    value::{Function, Type, Value},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InterpreterErrorKind {
     DivisionByZero,
+    /// result is greater than the largest representable value of its kind
+    Overflow(NumberKind),
+    /// result is less than the smallest representable value of its kind
+    Underflow(NumberKind),
+    /// integer literal does not fit in `i64`
+    LiteralOutOfRange,
+    UnboundVariable(String),
+    UnknownFunction(String),
+    ArityMismatch {
+        expected: usize,
+        found: usize,
+    },
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+    },
+    /// argument outside the domain of a built-in function, e.g. `sqrt(-1)`
+    DomainError(String),
+    /// the tree contains a part that failed to parse
+    SyntaxError,
+    /// evaluation took more steps than `EvalOptions::fuel`
+    OutOfFuel,
+    /// more calls in progress than `EvalOptions::max_depth`, e.g. through unbounded recursion
+    RecursionLimit,
 }
 pub type InterpreterError = Annotation<InterpreterErrorKind>;
+
+/// The kind of number a result is out of the range of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumberKind {
+    /// `i64`
+    Int,
+    /// integers of any size, although a power is limited to about 80,000 digits
+    BigInt,
+    /// fractions of two `i64`
+    Rational,
+    /// finite `f64`
+    Float,
+}
+
+/// Variables bound by `let` and `fn`, shared by every node of an evaluation.
+/// Functions capture a copy of it when they are defined.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    bindings: HashMap<String, Value>,
+}
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.bindings.get(name)
+    }
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.bindings.insert(name.to_string(), value);
+    }
+}
+
+/// What integer literals evaluate to, and so which arithmetic applies to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NumberMode {
+    /// `Value::Int`, where `7 / 2` truncates to `3`
+    #[default]
+    Machine,
+    /// `Value::Rational`, where `7 / 2` is exactly `7/2`
+    Rational,
+    /// `Value::BigInt`, where `2 ^ 100` is exact and literals beyond `u64` are accepted
+    BigInt,
+}
+
+/// How a single evaluation runs: its number mode, and limits so that untrusted input can
+/// neither run forever nor exhaust the native stack.
+#[derive(Debug, Clone)]
+pub struct EvalOptions {
+    pub numbers: NumberMode,
+    /// how many nodes may be evaluated, function bodies counting at every call; unlimited if `None`
+    pub fuel: Option<u64>,
+    /// how many function calls may be in progress at once, which guards the native stack:
+    /// a thread with a larger stack can allow more. The depth of the tree within each call is
+    /// bounded by `ParseOptions::max_tree_depth` instead. `bytecode` ignores it.
+    pub max_depth: usize,
+}
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            numbers: NumberMode::default(),
+            fuel: None,
+            // about two megabytes of stack in a debug build for calls of a few nodes each
+            max_depth: 128,
+        }
+    }
+}
+
+/// State shared by every node of an evaluation: the number mode and what is left of the
+/// limits of `EvalOptions`.
+#[derive(Debug)]
+pub struct Context {
+    numbers: NumberMode,
+    fuel: Option<u64>,
+    depth: usize,
+    max_depth: usize,
+}
+impl Context {
+    pub fn new(options: &EvalOptions) -> Self {
+        Self {
+            numbers: options.numbers,
+            fuel: options.fuel,
+            depth: 0,
+            max_depth: options.max_depth,
+        }
+    }
+
+    /// Takes one step.
+    pub fn step(&mut self) -> Result<(), InterpreterErrorKind> {
+        match &mut self.fuel {
+            Some(0) => Err(InterpreterErrorKind::OutOfFuel),
+            Some(fuel) => {
+                *fuel -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Takes one level of depth for a call, given back by `leave`.
+    pub fn enter(&mut self) -> Result<(), InterpreterErrorKind> {
+        if self.depth == self.max_depth {
+            return Err(InterpreterErrorKind::RecursionLimit);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
 pub struct Interpreter<'a> {
     ast: &'a Ast,
+    env: &'a mut Environment,
+    context: &'a mut Context,
 }
-type InterpreterResult = Result<i64, InterpreterError>;
+type InterpreterResult = Result<Value, InterpreterError>;
 impl<'a> Interpreter<'a> {
-    pub fn new(ast: &'a Ast) -> Interpreter<'a> {
-        Self { ast }
+    pub fn new(
+        ast: &'a Ast,
+        env: &'a mut Environment,
+        context: &'a mut Context,
+    ) -> Interpreter<'a> {
+        Self { ast, env, context }
+    }
+
+    pub fn eval(&mut self) -> InterpreterResult {
+        self.context
+            .step()
+            .map_err(|error_kind| InterpreterError::new(error_kind, self.ast.loc.clone()))?;
+        self.eval_node()
+    }
+
+    fn eval_child(&mut self, ast: &Ast) -> InterpreterResult {
+        Interpreter::new(ast, self.env, self.context).eval()
     }
 
-    pub fn eval(&self) -> Result<i64, InterpreterError> {
+    fn eval_node(&mut self) -> InterpreterResult {
         match &self.ast.value {
-            AstKind::Number(num) => Ok(*num as i64),
+            AstKind::Number(num) => match (i64::try_from(*num), self.context.numbers) {
+                (Ok(num), NumberMode::Machine) => Ok(Value::Int(num)),
+                (Ok(num), NumberMode::Rational) => Ok(Value::Rational(Rational::from(num))),
+                (_, NumberMode::BigInt) => Ok(Value::BigInt(BigInt::from(*num))),
+                (Err(_), _) => Err(InterpreterError::new(
+                    InterpreterErrorKind::LiteralOutOfRange,
+                    self.ast.loc.clone(),
+                )),
+            },
+            AstKind::BigNumber(num) if self.context.numbers == NumberMode::BigInt => {
+                Ok(Value::BigInt(num.clone()))
+            }
+            AstKind::BigNumber(_) => Err(InterpreterError::new(
+                InterpreterErrorKind::LiteralOutOfRange,
+                self.ast.loc.clone(),
+            )),
+            AstKind::Error => Err(InterpreterError::new(
+                InterpreterErrorKind::SyntaxError,
+                self.ast.loc.clone(),
+            )),
+            AstKind::Float(num) => Ok(Value::Float(num.0)),
+            AstKind::Bool(value) => Ok(Value::Bool(*value)),
             AstKind::UniOperator { operator, tree } => {
-                let num = Interpreter::new(&tree).eval()?;
-                Ok(self.eval_uni_operator(&operator, num))
+                if let Some(num) = Self::negated_literal(operator, tree, self.context.numbers) {
+                    return Ok(num);
+                }
+                let num = self.eval_child(tree)?;
+                Self::eval_uni_operator(operator, num)
+                    .map_err(|error_kind| InterpreterError::new(error_kind, self.ast.loc.clone()))
+            }
+            AstKind::BinOperator { operator, lhs, rhs }
+                if operator.value == BinOperatorKind::And
+                    || operator.value == BinOperatorKind::Or =>
+            {
+                // `&&` skips `rhs` when `lhs` is false, `||` when it is true
+                let left = self.eval_bool(lhs)?;
+                if left == (operator.value == BinOperatorKind::Or) {
+                    Ok(Value::Bool(left))
+                } else {
+                    self.eval_bool(rhs).map(Value::Bool)
+                }
             }
             AstKind::BinOperator { operator, lhs, rhs } => {
-                let left = Interpreter::new(&lhs).eval()?;
-                let right = Interpreter::new(&rhs).eval()?;
-                self.eval_bin_operator(&operator, left, right)
+                let left = self.eval_child(lhs)?;
+                let right = self.eval_child(rhs)?;
+                Self::eval_bin_operator(operator, left, right)
                     .map_err(|error_kind| InterpreterError::new(error_kind, self.ast.loc.clone()))
             }
+            AstKind::Variable(name) => self.lookup(name).ok_or_else(|| {
+                InterpreterError::new(
+                    InterpreterErrorKind::UnboundVariable(name.clone()),
+                    self.ast.loc.clone(),
+                )
+            }),
+            AstKind::Let { name, value } => {
+                let value = self.eval_child(value)?;
+                self.env.set(&name.value, value.clone());
+                Ok(value)
+            }
+            AstKind::FnDef { name, params, body } => {
+                let function = self.function(Some(&name.value), params, body);
+                self.env.set(&name.value, function.clone());
+                Ok(function)
+            }
+            AstKind::Lambda { params, body } => Ok(self.function(None, params, body)),
+            AstKind::Call { callee, args } => {
+                let function = match self.eval_child(callee) {
+                    // only the name being called is an unknown function, not those it is
+                    // computed from, as in `(if c then f else g)(1)`
+                    Err(InterpreterError {
+                        value: InterpreterErrorKind::UnboundVariable(name),
+                        loc,
+                    }) if callee.value == AstKind::Variable(name.clone()) => {
+                        return Err(InterpreterError::new(
+                            InterpreterErrorKind::UnknownFunction(name),
+                            loc,
+                        ))
+                    }
+                    function => function?,
+                };
+                match function {
+                    Value::Function(function) => self.call_function(function, args),
+                    Value::Builtin(builtin) => self.call_builtin(builtin, args),
+                    value => Err(InterpreterError::new(
+                        InterpreterErrorKind::TypeMismatch {
+                            expected: Type::Function,
+                            found: value.type_of(),
+                        },
+                        callee.loc.clone(),
+                    )),
+                }
+            }
+            AstKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                if self.eval_bool(cond)? {
+                    self.eval_child(then_branch)
+                } else {
+                    self.eval_child(else_branch)
+                }
+            }
+            AstKind::Program(stmts) => {
+                let (last, init) = stmts.split_last().expect("programs are never empty");
+                for stmt in init {
+                    self.eval_child(stmt)?;
+                }
+                self.eval_child(last)
+            }
         }
     }
 
-    fn eval_uni_operator(&self, uniop: &UniOperator, num: i64) -> i64 {
-        match uniop.value {
-            UniOperatorKind::Plus => num,
-            UniOperatorKind::Minus => -num,
+    fn eval_bool(&mut self, ast: &Ast) -> Result<bool, InterpreterError> {
+        match self.eval_child(ast)? {
+            Value::Bool(value) => Ok(value),
+            value => Err(InterpreterError::new(
+                InterpreterErrorKind::TypeMismatch {
+                    expected: Type::Bool,
+                    found: value.type_of(),
+                },
+                ast.loc.clone(),
+            )),
         }
     }
-    fn eval_bin_operator(
-        &self,
+
+    // bindings shadow built-in functions
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.env
+            .get(name)
+            .cloned()
+            .or_else(|| builtins::lookup(name).map(Value::Builtin))
+    }
+
+    fn call_function(&mut self, function: Rc<Function>, args: &[Ast]) -> InterpreterResult {
+        if function.params.len() != args.len() {
+            return Err(InterpreterError::new(
+                InterpreterErrorKind::ArityMismatch {
+                    expected: function.params.len(),
+                    found: args.len(),
+                },
+                self.ast.loc.clone(),
+            ));
+        }
+        let mut env = function.env.clone();
+        if let Some(name) = &function.name {
+            env.set(name, Value::Function(function.clone()));
+        }
+        for (param, arg) in function.params.iter().zip(args) {
+            let arg = self.eval_child(arg)?;
+            env.set(param, arg);
+        }
+        self.context
+            .enter()
+            .map_err(|error_kind| InterpreterError::new(error_kind, self.ast.loc.clone()))?;
+        let result = Interpreter::new(&function.body, &mut env, self.context).eval();
+        self.context.leave();
+        result
+    }
+
+    fn call_builtin(&mut self, builtin: &builtins::Builtin, args: &[Ast]) -> InterpreterResult {
+        builtin
+            .check_arity(args.len())
+            .map_err(|error_kind| InterpreterError::new(error_kind, self.ast.loc.clone()))?;
+        let args = args
+            .iter()
+            .map(|arg| self.eval_child(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        (builtin.function)(&args)
+            .map_err(|error_kind| InterpreterError::new(error_kind, self.ast.loc.clone()))
+    }
+
+    fn function(&self, name: Option<&str>, params: &[Identifier], body: &Ast) -> Value {
+        Value::Function(Rc::new(Function {
+            name: name.map(|name| name.to_string()),
+            params: params.iter().map(|param| param.value.clone()).collect(),
+            body: Rc::new(body.clone()),
+            env: self.env.clone(),
+        }))
+    }
+
+    /// Folds a minus applied directly to the literal `9223372036854775808`, so that
+    /// `-9223372036854775808` is `i64::MIN` instead of an out-of-range literal.
+    pub(crate) fn negated_literal(
+        operator: &UniOperator,
+        tree: &Ast,
+        numbers: NumberMode,
+    ) -> Option<Value> {
+        let is_min = operator.value == UniOperatorKind::Minus
+            && tree.value == AstKind::Number(i64::MIN.unsigned_abs());
+        match numbers {
+            NumberMode::Machine if is_min => Some(Value::Int(i64::MIN)),
+            NumberMode::Rational if is_min => Some(Value::Rational(Rational::from(i64::MIN))),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn eval_uni_operator(
+        uniop: &UniOperator,
+        num: Value,
+    ) -> Result<Value, InterpreterErrorKind> {
+        match (&uniop.value, num) {
+            (UniOperatorKind::Plus, num @ Value::Int(_))
+            | (UniOperatorKind::Plus, num @ Value::Float(_))
+            | (UniOperatorKind::Plus, num @ Value::Rational(_))
+            | (UniOperatorKind::Plus, num @ Value::BigInt(_)) => Ok(num),
+            (UniOperatorKind::Minus, Value::Int(num)) => num
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int)),
+            (UniOperatorKind::Minus, Value::Float(num)) => Ok(Value::Float(-num)),
+            (UniOperatorKind::Minus, Value::Rational(num)) => {
+                num.checked_neg().map(Value::Rational)
+            }
+            (UniOperatorKind::Minus, Value::BigInt(num)) => Ok(Value::BigInt(-&num)),
+            (UniOperatorKind::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+            (UniOperatorKind::Not, value) => Err(InterpreterErrorKind::TypeMismatch {
+                expected: Type::Bool,
+                found: value.type_of(),
+            }),
+            (_, value) => Err(InterpreterErrorKind::TypeMismatch {
+                expected: Type::Number,
+                found: value.type_of(),
+            }),
+        }
+    }
+
+    pub(crate) fn eval_bin_operator(
+        binop: &BinOperator,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, InterpreterErrorKind> {
+        // `Int ^ Int` is not always an `Int`, so it does not fit the promotion rule
+        match binop.value {
+            BinOperatorKind::Pow => return builtins::power(&left, &right),
+            BinOperatorKind::Eq
+            | BinOperatorKind::Ne
+            | BinOperatorKind::Lt
+            | BinOperatorKind::Le
+            | BinOperatorKind::Gt
+            | BinOperatorKind::Ge => return Self::eval_comparison(binop, &left, &right),
+            _ => {}
+        }
+        match (left, right) {
+            (Value::Int(left), Value::Int(right)) => {
+                Self::eval_int_bin_operator(binop, left, right).map(Value::Int)
+            }
+            (left @ Value::BigInt(_), right) | (left, right @ Value::BigInt(_)) => {
+                match (left.as_big_int(), right.as_big_int()) {
+                    (Some(left), Some(right)) => {
+                        Self::eval_big_int_bin_operator(binop, &left, &right).map(Value::BigInt)
+                    }
+                    _ => {
+                        let (left, right) = Self::as_f64_pair(&left, &right)?;
+                        Self::eval_float_bin_operator(binop, left, right).map(Value::Float)
+                    }
+                }
+            }
+            (left, right) => match (left.as_rational(), right.as_rational()) {
+                (Some(left), Some(right)) => {
+                    Self::eval_rational_bin_operator(binop, left, right).map(Value::Rational)
+                }
+                _ => {
+                    let (left, right) = Self::as_f64_pair(&left, &right)?;
+                    Self::eval_float_bin_operator(binop, left, right).map(Value::Float)
+                }
+            },
+        }
+    }
+
+    // numbers compare across `Int` and `Float`, booleans only support `==` and `!=`
+    fn eval_comparison(
+        binop: &BinOperator,
+        left: &Value,
+        right: &Value,
+    ) -> Result<Value, InterpreterErrorKind> {
+        let is_equality = binop.value == BinOperatorKind::Eq || binop.value == BinOperatorKind::Ne;
+        let ordering = match (left, right) {
+            (Value::Bool(left), Value::Bool(right)) if is_equality => left.partial_cmp(right),
+            (Value::Bool(_), right) if is_equality => {
+                return Err(InterpreterErrorKind::TypeMismatch {
+                    expected: Type::Bool,
+                    found: right.type_of(),
+                })
+            }
+            (Value::Int(left), Value::Int(right)) => left.partial_cmp(right),
+            (left @ Value::BigInt(_), right) | (left, right @ Value::BigInt(_)) => {
+                match (left.as_big_int(), right.as_big_int()) {
+                    (Some(left), Some(right)) => left.partial_cmp(&right),
+                    _ => {
+                        let (left, right) = Self::as_f64_pair(left, right)?;
+                        left.partial_cmp(&right)
+                    }
+                }
+            }
+            (left, right) => match (left.as_rational(), right.as_rational()) {
+                (Some(left), Some(right)) => left.partial_cmp(&right),
+                _ => {
+                    let (left, right) = Self::as_f64_pair(left, right)?;
+                    left.partial_cmp(&right)
+                }
+            },
+        };
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        let result = match binop.value {
+            BinOperatorKind::Eq => ordering == Some(Equal),
+            BinOperatorKind::Ne => ordering != Some(Equal),
+            BinOperatorKind::Lt => ordering == Some(Less),
+            BinOperatorKind::Le => ordering == Some(Less) || ordering == Some(Equal),
+            BinOperatorKind::Gt => ordering == Some(Greater),
+            BinOperatorKind::Ge => ordering == Some(Greater) || ordering == Some(Equal),
+            _ => unreachable!(),
+        };
+        Ok(Value::Bool(result))
+    }
+
+    fn eval_int_bin_operator(
         binop: &BinOperator,
         left: i64,
         right: i64,
     ) -> Result<i64, InterpreterErrorKind> {
+        // the sign of the exact result decides whether it went above `i64::MAX` or below `i64::MIN`
+        let out_of_range = |positive: bool| {
+            if positive {
+                InterpreterErrorKind::Overflow(NumberKind::Int)
+            } else {
+                InterpreterErrorKind::Underflow(NumberKind::Int)
+            }
+        };
+        match binop.value {
+            BinOperatorKind::Add => left
+                .checked_add(right)
+                .ok_or_else(|| out_of_range(right > 0)),
+            BinOperatorKind::Sub => left
+                .checked_sub(right)
+                .ok_or_else(|| out_of_range(right < 0)),
+            BinOperatorKind::Mul => left
+                .checked_mul(right)
+                .ok_or_else(|| out_of_range((left < 0) == (right < 0))),
+            BinOperatorKind::Div | BinOperatorKind::Mod | BinOperatorKind::FloorDiv
+                if right == 0 =>
+            {
+                Err(InterpreterErrorKind::DivisionByZero)
+            }
+            BinOperatorKind::Div => left
+                .checked_div(right)
+                .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int)),
+            // `i64::MIN % -1` is 0 but `checked_rem` reports it as an overflow
+            BinOperatorKind::Mod => {
+                let rem = left.checked_rem(right).unwrap_or(0);
+                if rem != 0 && (rem < 0) != (right < 0) {
+                    Ok(rem + right)
+                } else {
+                    Ok(rem)
+                }
+            }
+            BinOperatorKind::FloorDiv => {
+                let quot = left
+                    .checked_div(right)
+                    .ok_or(InterpreterErrorKind::Overflow(NumberKind::Int))?;
+                if left % right != 0 && (left < 0) != (right < 0) {
+                    Ok(quot - 1)
+                } else {
+                    Ok(quot)
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // both operands as floats, or the type of the first that is not a number
+    fn as_f64_pair(left: &Value, right: &Value) -> Result<(f64, f64), InterpreterErrorKind> {
+        match (left.as_f64(), right.as_f64()) {
+            (Some(left), Some(right)) => Ok((left, right)),
+            (None, _) => Err(InterpreterErrorKind::TypeMismatch {
+                expected: Type::Number,
+                found: left.type_of(),
+            }),
+            (_, None) => Err(InterpreterErrorKind::TypeMismatch {
+                expected: Type::Number,
+                found: right.type_of(),
+            }),
+        }
+    }
+
+    fn eval_rational_bin_operator(
+        binop: &BinOperator,
+        left: Rational,
+        right: Rational,
+    ) -> Result<Rational, InterpreterErrorKind> {
+        match binop.value {
+            BinOperatorKind::Add => left.checked_add(right),
+            BinOperatorKind::Sub => left.checked_sub(right),
+            BinOperatorKind::Mul => left.checked_mul(right),
+            BinOperatorKind::Div => left.checked_div(right),
+            BinOperatorKind::FloorDiv => left.checked_div(right).map(Rational::floor),
+            // the sign of the divisor, as with integers
+            BinOperatorKind::Mod => {
+                let quotient = left.checked_div(right)?.floor();
+                left.checked_sub(right.checked_mul(quotient)?)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // the same rounding as `eval_int_bin_operator`, without its overflows
+    fn eval_big_int_bin_operator(
+        binop: &BinOperator,
+        left: &BigInt,
+        right: &BigInt,
+    ) -> Result<BigInt, InterpreterErrorKind> {
+        let div_rem = || {
+            left.div_rem(right)
+                .ok_or(InterpreterErrorKind::DivisionByZero)
+        };
         match binop.value {
             BinOperatorKind::Add => Ok(left + right),
             BinOperatorKind::Sub => Ok(left - right),
             BinOperatorKind::Mul => Ok(left * right),
-            BinOperatorKind::Div => {
-                if right == 0 {
-                    Err(InterpreterErrorKind::DivisionByZero)
+            BinOperatorKind::Div => div_rem().map(|(quot, _)| quot),
+            BinOperatorKind::Mod => {
+                let (_, rem) = div_rem()?;
+                if !rem.is_zero() && rem.is_negative() != right.is_negative() {
+                    Ok(&rem + right)
                 } else {
-                    Ok(left / right)
+                    Ok(rem)
                 }
             }
+            BinOperatorKind::FloorDiv => {
+                let (quot, rem) = div_rem()?;
+                if !rem.is_zero() && left.is_negative() != right.is_negative() {
+                    Ok(&quot - &BigInt::from(1i64))
+                } else {
+                    Ok(quot)
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn eval_float_bin_operator(
+        binop: &BinOperator,
+        left: f64,
+        right: f64,
+    ) -> Result<f64, InterpreterErrorKind> {
+        let result = match binop.value {
+            BinOperatorKind::Add => left + right,
+            BinOperatorKind::Sub => left - right,
+            BinOperatorKind::Mul => left * right,
+            BinOperatorKind::Div | BinOperatorKind::Mod | BinOperatorKind::FloorDiv
+                if right == 0.0 =>
+            {
+                return Err(InterpreterErrorKind::DivisionByZero);
+            }
+            BinOperatorKind::Div => left / right,
+            BinOperatorKind::Mod => left - right * (left / right).floor(),
+            BinOperatorKind::FloorDiv => (left / right).floor(),
+            _ => unreachable!(),
+        };
+        if result == f64::INFINITY {
+            Err(InterpreterErrorKind::Overflow(NumberKind::Float))
+        } else if result == f64::NEG_INFINITY {
+            Err(InterpreterErrorKind::Underflow(NumberKind::Float))
+        } else {
+            Ok(result)
         }
     }
 }
 
 pub fn eval(ast: &Ast) -> InterpreterResult {
-    Interpreter::new(ast).eval()
+    eval_with(ast, &mut Environment::new())
+}
+
+pub fn eval_with(ast: &Ast, env: &mut Environment) -> InterpreterResult {
+    eval_with_options(ast, env, &EvalOptions::default())
+}
+
+pub fn eval_with_options(
+    ast: &Ast,
+    env: &mut Environment,
+    options: &EvalOptions,
+) -> InterpreterResult {
+    Interpreter::new(ast, env, &mut Context::new(options)).eval()
+}
+
+#[test]
+fn test_eval_numeric_tower() {
+    let eval_str = |s: &str| eval(&s.parse().unwrap());
+    assert_eq!(eval_str("7 / 2"), Ok(Value::Int(3)));
+    assert_eq!(eval_str("7 / 2.0"), Ok(Value::Float(3.5)));
+    assert_eq!(eval_str("1.5 * 2"), Ok(Value::Float(3.0)));
+    assert_eq!(eval_str("-(1 + 2.5e1)"), Ok(Value::Float(-26.0)));
+    assert_eq!(
+        eval_str("1 / (0.5 - 0.5)").map_err(|e| e.value),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
+}
+
+#[test]
+fn test_eval_checked_arithmetic() {
+    use crate::token::Loc;
+    let eval_str = |s: &str| eval(&s.parse().unwrap());
+    assert_eq!(
+        eval_str("1 + 9223372036854775807 * 2"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::Overflow(NumberKind::Int),
+            Loc::new(4, 27)
+        ))
+    );
+    assert_eq!(
+        eval_str("0 - 9223372036854775807 - 2"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::Underflow(NumberKind::Int),
+            Loc::new(0, 27)
+        ))
+    );
+    assert_eq!(eval_str("-9223372036854775808"), Ok(Value::Int(i64::MIN)));
+    assert_eq!(
+        eval_str("-9223372036854775808 + 1"),
+        Ok(Value::Int(i64::MIN + 1))
+    );
+    assert_eq!(
+        eval_str("-(9223372036854775807 + 1)").map_err(|e| e.value),
+        Err(InterpreterErrorKind::Overflow(NumberKind::Int))
+    );
+    assert_eq!(
+        eval_str("--9223372036854775808"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::Overflow(NumberKind::Int),
+            Loc::new(0, 21)
+        ))
+    );
+    assert_eq!(
+        eval_str("1 + 9223372036854775808"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::LiteralOutOfRange,
+            Loc::new(4, 23)
+        ))
+    );
+    assert_eq!(
+        eval_str("1e308 * 10").map_err(|e| e.value),
+        Err(InterpreterErrorKind::Overflow(NumberKind::Float))
+    );
+}
+
+#[test]
+fn test_eval_let() {
+    use crate::token::Loc;
+    let mut env = Environment::new();
+    let mut eval_str = |s: &str| eval_with(&s.parse().unwrap(), &mut env);
+    assert_eq!(eval_str("let rate = 1.5"), Ok(Value::Float(1.5)));
+    assert_eq!(eval_str("let total = rate * 4"), Ok(Value::Float(6.0)));
+    assert_eq!(eval_str("total - rate"), Ok(Value::Float(4.5)));
+    assert_eq!(
+        eval_str("total + discount"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::UnboundVariable("discount".to_string()),
+            Loc::new(8, 16)
+        ))
+    );
+}
+
+#[test]
+fn test_eval_functions() {
+    use crate::token::Loc;
+    let mut env = Environment::new();
+    let mut eval_str = |s: &str| eval_with(&s.parse().unwrap(), &mut env);
+    let me = eval_str("fn me(x) = me").unwrap();
+    assert_eq!(eval_str("me(1)(2)"), Ok(me));
+    eval_str("let base = 10").unwrap();
+    eval_str("fn scale(x) = x * base").unwrap();
+    eval_str("let base = 100").unwrap();
+    assert_eq!(eval_str("scale(2)"), Ok(Value::Int(20)));
+    assert_eq!(eval_str("(fn(a, b) = a - b)(5, 3)"), Ok(Value::Int(2)));
+    assert_eq!(eval_str("max(scale(3), 25, abs(-31))"), Ok(Value::Int(31)));
+    assert_eq!(
+        eval_str("1 + sqrt(4 - 5)"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::DomainError("sqrt is undefined for -1".to_string()),
+            Loc::new(4, 15)
+        ))
+    );
+    assert_eq!(
+        eval_str("scale(1, 2)"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::ArityMismatch {
+                expected: 1,
+                found: 2
+            },
+            Loc::new(0, 11)
+        ))
+    );
+    assert_eq!(
+        eval_str("1 + cube(2)"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::UnknownFunction("cube".to_string()),
+            Loc::new(4, 8)
+        ))
+    );
+    assert_eq!(
+        eval_str("(if c then sin else cos)(1)"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::UnboundVariable("c".to_string()),
+            Loc::new(4, 5)
+        ))
+    );
+    assert_eq!(
+        eval_str("scale + 1").map_err(|e| e.value),
+        Err(InterpreterErrorKind::TypeMismatch {
+            expected: Type::Number,
+            found: Type::Function
+        })
+    );
+}
+
+#[test]
+fn test_eval_power_modulo_floor_div() {
+    let eval_str = |s: &str| eval(&s.parse().unwrap());
+    assert_eq!(eval_str("-2^2"), Ok(Value::Int(-4)));
+    assert_eq!(eval_str("2**3^2"), Ok(Value::Int(512)));
+    assert_eq!(eval_str("2^-1"), Ok(Value::Float(0.5)));
+    assert_eq!(eval_str("2 * 3 ^ 2 % 5"), Ok(Value::Int(3)));
+    assert_eq!(eval_str("-7 % 3"), Ok(Value::Int(2)));
+    assert_eq!(eval_str("7 % -3"), Ok(Value::Int(-2)));
+    assert_eq!(eval_str("-7 // 2"), Ok(Value::Int(-4)));
+    assert_eq!(eval_str("7.5 // 2"), Ok(Value::Float(3.0)));
+    assert_eq!(eval_str("-7.5 % 2"), Ok(Value::Float(0.5)));
+    assert_eq!(
+        eval_str("1 % 0").map_err(|e| e.value),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
+    assert_eq!(
+        eval_str("1 // 0.0").map_err(|e| e.value),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
+    assert_eq!(
+        eval_str("0 ^ -1").map_err(|e| e.value),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
+    assert_eq!(eval_str("(-1) ^ 5000000001"), Ok(Value::Int(-1)));
+    assert_eq!(
+        eval_str("1 ^ 5000000000 + 0 ^ 5000000000"),
+        Ok(Value::Int(1))
+    );
+    assert_eq!(
+        eval_str("2 ^ 5000000000").map_err(|e| e.value),
+        Err(InterpreterErrorKind::Overflow(NumberKind::Int))
+    );
+}
+
+#[test]
+fn test_eval_conditionals() {
+    use crate::token::Loc;
+    let mut env = Environment::new();
+    let mut eval_str = |s: &str| eval_with(&s.parse().unwrap(), &mut env);
+    eval_str("fn fact(n) = if n <= 1 then 1 else n * fact(n - 1)").unwrap();
+    assert_eq!(eval_str("fact(10)"), Ok(Value::Int(3628800)));
+    assert_eq!(
+        eval_str("1 < 1.5 && !(2 == 2.0) || 3 != 3"),
+        Ok(Value::Bool(false))
+    );
+    assert_eq!(eval_str("false && 1 / 0 == 0"), Ok(Value::Bool(false)));
+    assert_eq!(eval_str("1 + if true then 2 else 3"), Ok(Value::Int(3)));
+    assert_eq!(
+        eval_str("true + 1"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::TypeMismatch {
+                expected: Type::Number,
+                found: Type::Bool
+            },
+            Loc::new(0, 8)
+        ))
+    );
+    assert_eq!(
+        eval_str("if 1 then 2 else 3"),
+        Err(InterpreterError::new(
+            InterpreterErrorKind::TypeMismatch {
+                expected: Type::Bool,
+                found: Type::Number
+            },
+            Loc::new(3, 4)
+        ))
+    );
+}
+
+#[test]
+fn test_eval_limits() {
+    let eval_str = |s: &str, options: &EvalOptions| {
+        eval_with_options(&s.parse().unwrap(), &mut Environment::new(), options)
+            .map_err(|e| e.value)
+    };
+    let defaults = EvalOptions::default();
+    let forever = "fn f(n) = f(n + 1); f(0)";
+    assert_eq!(
+        eval_str(forever, &defaults),
+        Err(InterpreterErrorKind::RecursionLimit)
+    );
+    let fuel = EvalOptions {
+        fuel: Some(100),
+        ..EvalOptions::default()
+    };
+    assert_eq!(
+        eval_str(forever, &fuel),
+        Err(InterpreterErrorKind::OutOfFuel)
+    );
+    assert_eq!(eval_str("1 + 2 * 3", &fuel), Ok(Value::Int(7)));
+    // only calls count against the depth, not the nodes of a long chain
+    let shallow = EvalOptions {
+        max_depth: 10,
+        ..EvalOptions::default()
+    };
+    let chain = format!("0{}", " + 1".repeat(300));
+    assert_eq!(eval_str(&chain, &shallow), Ok(Value::Int(300)));
+    let count = "fn count(n) = if n == 0 then 0 else 1 + count(n - 1); count(10)";
+    assert_eq!(
+        eval_str(count, &shallow),
+        Err(InterpreterErrorKind::RecursionLimit)
+    );
+    assert_eq!(
+        eval_str(&count.replace("10)", "9)"), &shallow),
+        Ok(Value::Int(9))
+    );
+    assert_eq!(eval_str(count, &defaults), Ok(Value::Int(10)));
+}
+
+#[test]
+fn test_eval_rational() {
+    let options = EvalOptions {
+        numbers: NumberMode::Rational,
+        ..EvalOptions::default()
+    };
+    let eval_str = |s: &str| {
+        eval_with_options(&s.parse().unwrap(), &mut Environment::new(), &options)
+            .map_err(|e| e.value)
+    };
+    let r = |n, d| Ok(Value::Rational(Rational::new(n, d).unwrap()));
+    assert_eq!(eval_str("7 / 2"), r(7, 2));
+    assert_eq!(eval_str("-7 // 2"), r(-4, 1));
+    assert_eq!(eval_str("-7 / 2 % 2"), r(1, 2));
+    assert_eq!(eval_str("(2 / 3) ^ -2"), r(9, 4));
+    assert_eq!(eval_str("round(5 / 2) + floor(-1 / 3)"), r(2, 1));
+    assert_eq!(eval_str("abs(-1 / 4) < 1 / 3"), Ok(Value::Bool(true)));
+    assert_eq!(eval_str("1 / 2 + 0.25"), Ok(Value::Float(0.75)));
+    assert_eq!(eval_str("gcd(6 / 2, 9)"), Ok(Value::Int(3)));
+    assert_eq!(
+        eval_str("1 / (1 - 1)"),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
+    assert_eq!(
+        eval_str("1 / 3 * 9223372036854775807 * 4"),
+        Err(InterpreterErrorKind::Overflow(NumberKind::Rational))
+    );
+}
+
+#[test]
+fn test_eval_big_integers() {
+    let options = EvalOptions {
+        numbers: NumberMode::BigInt,
+        ..EvalOptions::default()
+    };
+    let eval_str = |s: &str| {
+        let tokens = crate::token::lex_with(s, &crate::token::LexOptions { big_integers: true });
+        eval_with_options(
+            &crate::ast::parse(tokens.unwrap()).unwrap(),
+            &mut Environment::new(),
+            &options,
+        )
+        .map(|value| value.to_string())
+        .map_err(|e| e.value)
+    };
+    let ok = |s: &str| Ok(s.to_string());
+    assert_eq!(
+        eval_str("2 ^ 200"),
+        ok("1606938044258990275541962092341162602522202993782792835301376")
+    );
+    assert_eq!(
+        eval_str("fn fact(n) = if n == 0 then 1 else n * fact(n - 1); fact(25)"),
+        ok("15511210043330985984000000")
+    );
+    assert_eq!(
+        eval_str("123456789012345678901234567890 * -98765432109876543210"),
+        ok("-12193263113702179522496570642237463801111263526900")
+    );
+    assert_eq!(eval_str("-(2 ^ 70) // 3 % 1000"), ok("858"));
+    assert_eq!(eval_str("-7 % 2 + -7 // 2"), ok("-3"));
+    assert_eq!(eval_str("2 ^ 64 > 2 ^ 63"), ok("true"));
+    assert_eq!(eval_str("2 ^ 64 / 0.5"), ok("3.6893488147419103e19"));
+    assert_eq!(eval_str("abs(-(10 ^ 20))"), ok("100000000000000000000"));
+    assert_eq!(
+        eval_str("10 ^ 20 % (1 - 1)"),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
 }
@@ -0,0 +1,801 @@
+//! A small JSON value with a reader and a writer, and the stable JSON form of tokens,
+//! trees and errors.
+//!
+//! Every node is an object with a `kind` and a `loc` (`{"start": 0, "end": 1}`, byte offsets)
+//! and the fields of its `AstKind` variant under the same names, e.g.
+//!
+//! ```text
+//! {"kind": "BinOperator",
+//!  "operator": {"kind": "Add", "loc": {"start": 2, "end": 3}},
+//!  "lhs": {"kind": "Number", "value": 1, "loc": {"start": 0, "end": 1}},
+//!  "rhs": {"kind": "Number", "value": 2, "loc": {"start": 4, "end": 5}},
+//!  "loc": {"start": 0, "end": 5}}
+//! ```
+//!
+//! Integers beyond `u64` are written as strings of digits.
+//!
+//! Reading is recursive, so it stops at a limit of nesting: that of the trees the parser
+//! builds with `ParseOptions::default()`, and for JSON values a little more, since the
+//! arguments of a call are nested in an array and operators and locations in objects.
+use crate::{
+    ast::{
+        Ast, AstKind, BinOperator, BinOperatorKind, Identifier, ParseOptions, UniOperator,
+        UniOperatorKind,
+    },
+    diagnostics::Diagnostic,
+    errors::CompileError,
+    token::{Annotation, Float, Loc, Token, TokenKind},
+    value::Value,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// the literal as written, so that integers keep their precision
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    /// keys in insertion order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string(s: impl Into<String>) -> Self {
+        Json::String(s.into())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Reads a JSON document; anything but whitespace after the value is an error.
+    pub fn parse(input: &str) -> Result<Json, JsonError> {
+        let mut reader = Reader {
+            input: input.as_bytes(),
+            position: 0,
+            depth: 0,
+            // each of the calls nested in a tree puts its arguments in an array, and a leaf
+            // has its location in the object of its operator
+            max_depth: max_tree_depth() + ParseOptions::default().max_depth + 2,
+        };
+        let json = reader.value()?;
+        reader.skip_whitespace();
+        if reader.position < input.len() {
+            return Err(reader.error("trailing characters"));
+        }
+        Ok(json)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(n: u64) -> Self {
+        Json::Number(n.to_string())
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n.to_string())
+    }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Self {
+        if n.is_finite() {
+            Json::Number(format!("{:?}", n))
+        } else {
+            Json::Null
+        }
+    }
+}
+
+/// Writes compact JSON.
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => f.write_str(n),
+            Json::String(s) => write_string(s, f),
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(fields) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(key, f)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_string(s: &str, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// Malformed JSON, or JSON that does not describe a tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonError {
+    pub message: String,
+}
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+fn error<T>(message: String) -> Result<T, JsonError> {
+    Err(JsonError { message })
+}
+
+// how deep a tree the parser builds by default may be, with a few levels to spare for the
+// statements of a program
+fn max_tree_depth() -> usize {
+    let options = ParseOptions::default();
+    options.max_depth + options.max_tree_depth + 4
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+    /// how many arrays and objects enclose the current position
+    depth: usize,
+    max_depth: usize,
+}
+impl<'a> Reader<'a> {
+    fn error(&self, message: &str) -> JsonError {
+        JsonError {
+            message: format!("{} at byte {}", message, self.position),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.input.len() && b" \t\r\n".contains(&self.input[self.position]) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.position).copied()
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), JsonError> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected as char)))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, json: Json) -> Result<Json, JsonError> {
+        if self.input[self.position..].starts_with(keyword.as_bytes()) {
+            self.position += keyword.len();
+            Ok(json)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    // value = object | array | string | number | "true" | "false" | "null" ;
+    fn value(&mut self) -> Result<Json, JsonError> {
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn nested(&mut self, f: fn(&mut Self) -> Result<Json, JsonError>) -> Result<Json, JsonError> {
+        if self.depth == self.max_depth {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let json = f(self);
+        self.depth -= 1;
+        json
+    }
+
+    // object = "{" (string ":" value ("," string ":" value)*)? "}" ;
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            fields.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                _ => break,
+            }
+        }
+        self.expect(b'}')?;
+        Ok(Json::Object(fields))
+    }
+
+    // array = "[" (value ("," value)*)? "]" ;
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                _ => break,
+            }
+        }
+        self.expect(b']')?;
+        Ok(Json::Array(items))
+    }
+
+    // number = "-"? digit+ ("." digit+)? (("e" | "E") ("+" | "-")? digit+)? ;
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.position;
+        let digits = |reader: &mut Self| {
+            let from = reader.position;
+            while reader.position < reader.input.len()
+                && reader.input[reader.position].is_ascii_digit()
+            {
+                reader.position += 1;
+            }
+            reader.position > from
+        };
+        if self.input[self.position] == b'-' {
+            self.position += 1;
+        }
+        let mut valid = digits(self);
+        if self.input.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            valid &= digits(self);
+        }
+        if let Some(b'e') | Some(b'E') = self.input.get(self.position) {
+            self.position += 1;
+            if let Some(b'+') | Some(b'-') = self.input.get(self.position) {
+                self.position += 1;
+            }
+            valid &= digits(self);
+        }
+        if !valid {
+            return Err(self.error("malformed number"));
+        }
+        let literal = std::str::from_utf8(&self.input[start..self.position]).unwrap();
+        Ok(Json::Number(literal.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = match self.input.get(self.position) {
+                None => return Err(self.error("unterminated string")),
+                Some(&byte) => byte,
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.input.get(self.position).copied();
+                    self.position += 1;
+                    let escaped = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut buffer).as_bytes());
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    // the hex digits after `\u`, with a second escape for a surrogate pair
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let hex = |reader: &mut Self| {
+            let digits = reader
+                .input
+                .get(reader.position..reader.position + 4)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| reader.error("invalid unicode escape"))?;
+            reader.position += 4;
+            Ok(digits)
+        };
+        let high = hex(self)?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.input[self.position..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.position += 2;
+            let low = hex(self)?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        std::char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+}
+
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+impl ToJson for Loc {
+    fn to_json(&self) -> Json {
+        Json::object(vec![("start", self.start.into()), ("end", self.end.into())])
+    }
+}
+
+// `{"kind": ..., <fields>, "loc": ...}`
+fn node<T>(kind: &str, annotation: &Annotation<T>, mut fields: Vec<(&str, Json)>) -> Json {
+    fields.insert(0, ("kind", Json::string(kind)));
+    fields.push(("loc", annotation.loc.to_json()));
+    Json::object(fields)
+}
+
+impl ToJson for Token {
+    fn to_json(&self) -> Json {
+        let kind = format!("{:?}", self.value);
+        match &self.value {
+            TokenKind::Number(n) => node("Number", self, vec![("value", (*n).into())]),
+            TokenKind::BigNumber(n) => node(
+                "BigNumber",
+                self,
+                vec![("value", Json::string(n.to_string()))],
+            ),
+            TokenKind::Float(Float(n)) => node("Float", self, vec![("value", (*n).into())]),
+            TokenKind::Ident(name) => node("Ident", self, vec![("value", Json::string(name))]),
+            _ => node(&kind, self, vec![]),
+        }
+    }
+}
+
+impl ToJson for Identifier {
+    fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("value", Json::string(&self.value)),
+            ("loc", self.loc.to_json()),
+        ])
+    }
+}
+
+impl ToJson for Ast {
+    fn to_json(&self) -> Json {
+        let all = |asts: &[Ast]| Json::Array(asts.iter().map(ToJson::to_json).collect());
+        let names =
+            |params: &[Identifier]| Json::Array(params.iter().map(ToJson::to_json).collect());
+        match &self.value {
+            AstKind::Number(n) => node("Number", self, vec![("value", (*n).into())]),
+            AstKind::BigNumber(n) => node(
+                "BigNumber",
+                self,
+                vec![("value", Json::string(n.to_string()))],
+            ),
+            AstKind::Float(Float(n)) => node("Float", self, vec![("value", (*n).into())]),
+            AstKind::Bool(b) => node("Bool", self, vec![("value", Json::Bool(*b))]),
+            AstKind::UniOperator { operator, tree } => node(
+                "UniOperator",
+                self,
+                vec![
+                    (
+                        "operator",
+                        node(&format!("{:?}", operator.value), operator, vec![]),
+                    ),
+                    ("tree", tree.to_json()),
+                ],
+            ),
+            AstKind::BinOperator { operator, lhs, rhs } => node(
+                "BinOperator",
+                self,
+                vec![
+                    (
+                        "operator",
+                        node(&format!("{:?}", operator.value), operator, vec![]),
+                    ),
+                    ("lhs", lhs.to_json()),
+                    ("rhs", rhs.to_json()),
+                ],
+            ),
+            AstKind::Variable(name) => node("Variable", self, vec![("name", Json::string(name))]),
+            AstKind::Let { name, value } => node(
+                "Let",
+                self,
+                vec![("name", name.to_json()), ("value", value.to_json())],
+            ),
+            AstKind::FnDef { name, params, body } => node(
+                "FnDef",
+                self,
+                vec![
+                    ("name", name.to_json()),
+                    ("params", names(params)),
+                    ("body", body.to_json()),
+                ],
+            ),
+            AstKind::Lambda { params, body } => node(
+                "Lambda",
+                self,
+                vec![("params", names(params)), ("body", body.to_json())],
+            ),
+            AstKind::Call { callee, args } => node(
+                "Call",
+                self,
+                vec![("callee", callee.to_json()), ("args", all(args))],
+            ),
+            AstKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => node(
+                "If",
+                self,
+                vec![
+                    ("cond", cond.to_json()),
+                    ("then_branch", then_branch.to_json()),
+                    ("else_branch", else_branch.to_json()),
+                ],
+            ),
+            AstKind::Program(stmts) => node("Program", self, vec![("stmts", all(stmts))]),
+            AstKind::Error => node("Error", self, vec![]),
+        }
+    }
+}
+
+/// `{"code", "message", "labels": [{"loc", "message", "primary"}], "notes", "help"}`
+impl ToJson for Diagnostic {
+    fn to_json(&self) -> Json {
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| {
+                Json::object(vec![
+                    ("loc", label.loc.to_json()),
+                    ("message", Json::string(&label.message)),
+                    ("primary", Json::Bool(label.primary)),
+                ])
+            })
+            .collect();
+        let notes = self.notes.iter().map(Json::string).collect();
+        Json::object(vec![
+            ("code", Json::string(self.code)),
+            ("message", Json::string(&self.message)),
+            ("labels", Json::Array(labels)),
+            ("notes", Json::Array(notes)),
+            ("help", self.help.as_ref().map_or(Json::Null, Json::string)),
+        ])
+    }
+}
+
+/// The diagnostic for `error` with the stage that reported it: `lexer`, `parser`, `type` or
+/// `runtime`.
+/// `source` places errors without a location, as in `Diagnostic::from_error`.
+pub fn error_to_json(error: &CompileError, source: &str) -> Json {
+    let stage = match error {
+        CompileError::Lexer(_) => "lexer",
+        CompileError::Parser(_) => "parser",
+        CompileError::Type(_) => "type",
+        CompileError::Interpret(_) => "runtime",
+    };
+    match Diagnostic::from_error(error, source).to_json() {
+        Json::Object(mut fields) => {
+            fields.insert(0, ("stage".to_string(), Json::string(stage)));
+            Json::Object(fields)
+        }
+        _ => unreachable!(),
+    }
+}
+
+impl ToJson for CompileError {
+    fn to_json(&self) -> Json {
+        error_to_json(self, "")
+    }
+}
+
+impl ToJson for crate::interpreter::InterpreterError {
+    fn to_json(&self) -> Json {
+        error_to_json(&CompileError::Interpret(self.clone()), "")
+    }
+}
+
+/// Numbers and booleans as JSON values, fractions and functions as their description.
+impl ToJson for Value {
+    fn to_json(&self) -> Json {
+        match self {
+            Value::Int(n) => Json::Number(n.to_string()),
+            Value::Float(n) => (*n).into(),
+            Value::Rational(n) if n.is_integer() => Json::Number(n.to_string()),
+            Value::Rational(_) => Json::string(self.to_string()),
+            Value::BigInt(n) => Json::Number(n.to_string()),
+            Value::Bool(b) => Json::Bool(*b),
+            Value::Function(_) | Value::Builtin(_) => Json::string(self.to_string()),
+        }
+    }
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> Result<&'a Json, JsonError> {
+    json.get(key)
+        .map_or_else(|| error(format!("missing field '{}' in {}", key, json)), Ok)
+}
+
+fn string_field<'a>(json: &'a Json, key: &str) -> Result<&'a str, JsonError> {
+    match field(json, key)? {
+        Json::String(s) => Ok(s),
+        other => error(format!("'{}' must be a string, found {}", key, other)),
+    }
+}
+
+fn array_field<'a>(json: &'a Json, key: &str) -> Result<&'a [Json], JsonError> {
+    match field(json, key)? {
+        Json::Array(items) => Ok(items),
+        other => error(format!("'{}' must be an array, found {}", key, other)),
+    }
+}
+
+fn number<T: std::str::FromStr>(json: &Json) -> Result<T, JsonError> {
+    match json {
+        Json::Number(n) => n
+            .parse()
+            .map_or_else(|_| error(format!("{} is out of range", n)), Ok),
+        other => error(format!("expected a number, found {}", other)),
+    }
+}
+
+fn loc_from_json(json: &Json) -> Result<Loc, JsonError> {
+    let loc = field(json, "loc")?;
+    Ok(Loc::new(
+        number(field(loc, "start")?)?,
+        number(field(loc, "end")?)?,
+    ))
+}
+
+fn identifier_from_json(json: &Json) -> Result<Identifier, JsonError> {
+    Ok(Identifier::new(
+        string_field(json, "value")?.to_string(),
+        loc_from_json(json)?,
+    ))
+}
+
+fn uni_operator_from_json(json: &Json) -> Result<UniOperator, JsonError> {
+    let kind = match string_field(json, "kind")? {
+        "Plus" => UniOperatorKind::Plus,
+        "Minus" => UniOperatorKind::Minus,
+        "Not" => UniOperatorKind::Not,
+        other => return error(format!("unknown unary operator '{}'", other)),
+    };
+    Ok(UniOperator::new(kind, loc_from_json(json)?))
+}
+
+fn bin_operator_from_json(json: &Json) -> Result<BinOperator, JsonError> {
+    let kind = match string_field(json, "kind")? {
+        "Add" => BinOperatorKind::Add,
+        "Sub" => BinOperatorKind::Sub,
+        "Mul" => BinOperatorKind::Mul,
+        "Div" => BinOperatorKind::Div,
+        "Mod" => BinOperatorKind::Mod,
+        "Pow" => BinOperatorKind::Pow,
+        "FloorDiv" => BinOperatorKind::FloorDiv,
+        "Eq" => BinOperatorKind::Eq,
+        "Ne" => BinOperatorKind::Ne,
+        "Lt" => BinOperatorKind::Lt,
+        "Le" => BinOperatorKind::Le,
+        "Gt" => BinOperatorKind::Gt,
+        "Ge" => BinOperatorKind::Ge,
+        "And" => BinOperatorKind::And,
+        "Or" => BinOperatorKind::Or,
+        other => return error(format!("unknown binary operator '{}'", other)),
+    };
+    Ok(BinOperator::new(kind, loc_from_json(json)?))
+}
+
+/// Reads a tree written by `Ast::to_json`.
+pub fn ast_from_json(json: &Json) -> Result<Ast, JsonError> {
+    tree_from_json(json, max_tree_depth())
+}
+
+// `ast_from_json` for a tree at most `depth` levels deep below its root
+fn tree_from_json(json: &Json, depth: usize) -> Result<Ast, JsonError> {
+    let child = |json: &Json| match depth {
+        0 => error("tree nested too deeply".to_string()),
+        _ => tree_from_json(json, depth - 1),
+    };
+    let ast = |key| field(json, key).and_then(child).map(Box::new);
+    let asts = |key| {
+        array_field(json, key)?
+            .iter()
+            .map(child)
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let params = || {
+        array_field(json, "params")?
+            .iter()
+            .map(identifier_from_json)
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let kind = match string_field(json, "kind")? {
+        "Number" => AstKind::Number(number(field(json, "value")?)?),
+        "BigNumber" => match string_field(json, "value")?.parse() {
+            Ok(n) => AstKind::BigNumber(n),
+            Err(_) => return error(format!("malformed big number in {}", json)),
+        },
+        // the lexer never produces an infinite or NaN literal
+        "Float" => match number(field(json, "value")?)? {
+            num if f64::is_finite(num) => AstKind::Float(Float(num)),
+            num => return error(format!("float literal {} is not finite", num)),
+        },
+        "Bool" => match field(json, "value")? {
+            Json::Bool(b) => AstKind::Bool(*b),
+            other => return error(format!("expected a boolean, found {}", other)),
+        },
+        "UniOperator" => AstKind::UniOperator {
+            operator: uni_operator_from_json(field(json, "operator")?)?,
+            tree: ast("tree")?,
+        },
+        "BinOperator" => AstKind::BinOperator {
+            operator: bin_operator_from_json(field(json, "operator")?)?,
+            lhs: ast("lhs")?,
+            rhs: ast("rhs")?,
+        },
+        "Variable" => AstKind::Variable(string_field(json, "name")?.to_string()),
+        "Let" => AstKind::Let {
+            name: identifier_from_json(field(json, "name")?)?,
+            value: ast("value")?,
+        },
+        "FnDef" => AstKind::FnDef {
+            name: identifier_from_json(field(json, "name")?)?,
+            params: params()?,
+            body: ast("body")?,
+        },
+        "Lambda" => AstKind::Lambda {
+            params: params()?,
+            body: ast("body")?,
+        },
+        "Call" => AstKind::Call {
+            callee: ast("callee")?,
+            args: asts("args")?,
+        },
+        "If" => AstKind::If {
+            cond: ast("cond")?,
+            then_branch: ast("then_branch")?,
+            else_branch: ast("else_branch")?,
+        },
+        "Program" => match asts("stmts")? {
+            stmts if stmts.is_empty() => return error("a program needs a statement".to_string()),
+            stmts => AstKind::Program(stmts),
+        },
+        "Error" => AstKind::Error,
+        other => return error(format!("unknown node kind '{}'", other)),
+    };
+    Ok(Ast::new(kind, loc_from_json(json)?))
+}
+
+#[test]
+fn test_json() {
+    assert_eq!(
+        Json::parse(r#" {"a": [1, -2.5e3, true, null], "b\n": "é😀"} "#),
+        Ok(Json::object(vec![
+            (
+                "a",
+                Json::Array(vec![
+                    Json::Number("1".to_string()),
+                    Json::Number("-2.5e3".to_string()),
+                    Json::Bool(true),
+                    Json::Null
+                ])
+            ),
+            ("b\n", Json::string("é😀")),
+        ]))
+    );
+    assert!(Json::parse("[1,]").is_err());
+    assert!(Json::parse("{} x").is_err());
+
+    let ast: Ast = "fn f(x) = if x > 1.5 then -x else g(x, true)"
+        .parse()
+        .unwrap();
+    let json = ast.to_json().to_string();
+    assert_eq!(ast_from_json(&Json::parse(&json).unwrap()), Ok(ast));
+    let ast: Ast = "1 + 2".parse().unwrap();
+    assert_eq!(
+        ast.to_json().to_string(),
+        r#"{"kind":"BinOperator","operator":{"kind":"Add","loc":{"start":2,"end":3}},"lhs":{"kind":"Number","value":1,"loc":{"start":0,"end":1}},"rhs":{"kind":"Number","value":2,"loc":{"start":4,"end":5}},"loc":{"start":0,"end":5}}"#
+    );
+
+    // untrusted input is read to a limit of nesting rather than to a stack overflow
+    assert_eq!(
+        Json::parse(&"[".repeat(200_000)),
+        Err(JsonError {
+            message: "nesting too deep at byte 1134".to_string()
+        })
+    );
+    let number = |value: &str| {
+        format!(
+            r#"{{"kind":"Float","value":{},"loc":{{"start":0,"end":1}}}}"#,
+            value
+        )
+    };
+    let mut source = number("1.5");
+    for _ in 0..20 {
+        source = format!(
+            r#"{{"kind":"UniOperator","operator":{{"kind":"Minus","loc":{{"start":0,"end":1}}}},"tree":{},"loc":{{"start":0,"end":1}}}}"#,
+            source
+        );
+    }
+    let json = Json::parse(&source).unwrap();
+    assert!(tree_from_json(&json, 20).is_ok());
+    assert_eq!(
+        tree_from_json(&json, 19),
+        Err(JsonError {
+            message: "tree nested too deeply".to_string()
+        })
+    );
+    assert_eq!(
+        ast_from_json(&Json::parse(&number("1e999")).unwrap()),
+        Err(JsonError {
+            message: "float literal inf is not finite".to_string()
+        })
+    );
+}
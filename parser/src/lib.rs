@@ -0,0 +1,29 @@
+pub mod ast;
+pub mod bigint;
+pub mod builtins;
+pub mod bytecode;
+pub mod derive;
+pub mod diagnostics;
+pub mod errors;
+pub mod interpreter;
+pub mod json;
+pub mod optimize;
+pub mod pretty;
+pub mod printer;
+pub mod rational;
+pub mod rpn;
+pub mod session;
+pub mod source_map;
+pub mod token;
+pub mod typecheck;
+pub mod value;
+pub mod visit;
+
+impl std::str::FromStr for ast::Ast {
+    type Err = errors::CompileError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = token::lex(s)?;
+        let ast = ast::parse(tokens)?;
+        Ok(ast)
+    }
+}
@@ -1,75 +1,423 @@
-mod ast;
-mod errors;
-mod interpreter;
-mod token;
+use parser::{
+    derive,
+    diagnostics::Diagnostic,
+    errors,
+    json::{self, Json, ToJson},
+    printer::{self, OutputMode},
+    rpn,
+    session::{Session, Settings},
+};
+use std::process::exit;
+
+const USAGE: &str = "usage: parser [--output <mode>] [--format <format>] [--depth <calls>]
+              [--length <terms>] [-e <expr> | <file> | -]
+  -e <expr>          evaluate <expr> and print its value
+  <file>             evaluate the program in <file>, `-` reads it from stdin
+  --output <mode>    value (default), tokens, tree, sexpr or verbose
+  --format <format>  text (default), or json to print one object with the outputs or errors
+  --depth <calls>    how many function calls may be in progress, 10000 by default
+  --length <terms>   how long a chain such as `1 + 2 + 3` may be, 10000 by default
+with no arguments, starts a REPL, or reads a program from stdin when it is not a terminal";
+
+// the stack `main` runs on, reserved rather than used up front. It lets the limits below
+// exceed the library's defaults, which fit the two megabytes of a spawned thread.
+const STACK_SIZE: usize = 1 << 30;
+const DEFAULT_MAX_DEPTH: usize = 10_000;
+const DEFAULT_MAX_TREE_DEPTH: usize = 10_000;
+
+// exit codes
+const EXIT_USAGE: i32 = 1;
+const EXIT_LEX_ERROR: i32 = 2;
+const EXIT_PARSE_ERROR: i32 = 3;
+const EXIT_RUNTIME_ERROR: i32 = 4;
+const EXIT_TYPE_ERROR: i32 = 5;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Input {
+    Help,
+    Interactive,
+    Expr(String),
+    File(String),
+    Stdin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    input: Input,
+    output: OutputMode,
+    format: Format,
+    /// `Settings::max_depth`
+    max_depth: usize,
+    /// `Settings::max_tree_depth`
+    max_tree_depth: usize,
+}
+
+impl Options {
+    fn settings(&self) -> Settings {
+        Settings {
+            max_depth: self.max_depth,
+            max_tree_depth: self.max_tree_depth,
+            ..Settings::default()
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut input = None;
+    let mut output = OutputMode::default();
+    let mut format = Format::default();
+    let mut max_depth = DEFAULT_MAX_DEPTH;
+    let mut max_tree_depth = DEFAULT_MAX_TREE_DEPTH;
+    let limit = |arg: &str, value: Option<String>| match value.map(|value| value.parse()) {
+        Some(Ok(n)) if n > 0 => Ok(n),
+        _ => Err(format!("{} requires a positive number", arg)),
+    };
+    while let Some(arg) = args.next() {
+        let next = match arg.as_str() {
+            "-h" | "--help" => Input::Help,
+            "--output" | "-o" => {
+                let mode = args.next().ok_or("--output requires a mode")?;
+                output = mode.parse()?;
+                continue;
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    _ => return Err("--format requires text or json".to_string()),
+                };
+                continue;
+            }
+            "--depth" => {
+                max_depth = limit(&arg, args.next())?;
+                continue;
+            }
+            "--length" => {
+                max_tree_depth = limit(&arg, args.next())?;
+                continue;
+            }
+            "-e" => Input::Expr(args.next().ok_or("-e requires an expression")?),
+            "-" => Input::Stdin,
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => Input::File(arg),
+        };
+        if input.is_some() {
+            return Err(format!("unexpected argument: {:?}", next));
+        }
+        input = Some(next);
+    }
+    let input = input.unwrap_or_else(|| {
+        use std::io::IsTerminal;
+        if std::io::stdin().is_terminal() {
+            Input::Interactive
+        } else {
+            Input::Stdin
+        }
+    });
+    Ok(Options {
+        input,
+        output,
+        format,
+        max_depth,
+        max_tree_depth,
+    })
+}
+
 fn main() {
-    parse_interactive();
+    let main = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_main)
+        .expect("failed to start the main thread");
+    if main.join().is_err() {
+        exit(EXIT_USAGE)
+    }
+}
+
+fn run_main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(EXIT_USAGE)
+    });
+    let session = Session::with_settings(options.settings());
+    let (source, filename) = match options.input {
+        Input::Help => return println!("{}", USAGE),
+        Input::Interactive => return parse_interactive(options.output, options.settings()),
+        Input::Expr(expr) => (expr, "<expr>".to_string()),
+        Input::File(path) => match std::fs::read_to_string(&path) {
+            Ok(source) => (source, path),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                exit(EXIT_USAGE)
+            }
+        },
+        Input::Stdin => {
+            use std::io::Read;
+            let mut source = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut source) {
+                eprintln!("<stdin>: {}", e);
+                exit(EXIT_USAGE)
+            }
+            (source, "<stdin>".to_string())
+        }
+    };
+    exit(match options.format {
+        Format::Text => run(session, &source, &filename, options.output),
+        Format::Json => run_json(session, &source, options.output),
+    })
+}
+
+/// Evaluates `source` as a whole program and prints its value, returning the exit code.
+fn run(mut session: Session, source: &str, filename: &str, output: OutputMode) -> i32 {
+    if output.shows_tokens() {
+        if let Ok(tokens) = session.lex(source) {
+            print!("{}", printer::tokens(&tokens));
+        }
+    }
+    let result = session.parse(source).and_then(|ast| {
+        print_ast(&ast, output);
+        session.eval(source, &ast).map_err(|e| vec![e.into()])
+    });
+    match result {
+        Ok(value) => {
+            println!("{}", value);
+            0
+        }
+        Err(compile_errors) => {
+            for compile_error in &compile_errors {
+                let diagnostic = Diagnostic::from_error(compile_error, source);
+                eprint!("{}", diagnostic.render(source, Some(filename)));
+            }
+            exit_code(&compile_errors[0])
+        }
+    }
+}
+
+/// `run` writing a single JSON object: `tokens` and `ast` when `output` asks for them,
+/// then `value`, or `errors` on failure.
+fn run_json(mut session: Session, source: &str, output: OutputMode) -> i32 {
+    let mut fields = Vec::new();
+    if output.shows_tokens() {
+        if let Ok(tokens) = session.lex(source) {
+            let tokens = tokens.iter().map(ToJson::to_json).collect();
+            fields.push(("tokens", Json::Array(tokens)));
+        }
+    }
+    let result = session.parse(source).and_then(|ast| {
+        if output.shows_tree() || output.shows_sexpr() {
+            fields.push(("ast", ast.to_json()));
+        }
+        session.eval(source, &ast).map_err(|e| vec![e.into()])
+    });
+    let code = match result {
+        Ok(value) => {
+            fields.push(("value", value.to_json()));
+            0
+        }
+        Err(compile_errors) => {
+            let errors = compile_errors
+                .iter()
+                .map(|e| json::error_to_json(e, source))
+                .collect();
+            fields.push(("errors", Json::Array(errors)));
+            exit_code(&compile_errors[0])
+        }
+    };
+    println!("{}", Json::object(fields));
+    code
+}
+
+fn exit_code(error: &errors::CompileError) -> i32 {
+    match error {
+        errors::CompileError::Lexer(_) => EXIT_LEX_ERROR,
+        errors::CompileError::Parser(_) => EXIT_PARSE_ERROR,
+        errors::CompileError::Type(_) => EXIT_TYPE_ERROR,
+        errors::CompileError::Interpret(_) => EXIT_RUNTIME_ERROR,
+    }
 }
 
-impl std::str::FromStr for ast::Ast {
-    type Err = errors::CompileError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = token::lex(s)?;
-        let ast = ast::parse(tokens)?;
-        Ok(ast)
+fn print_ast(ast: &parser::ast::Ast, output: OutputMode) {
+    if output.shows_tree() {
+        print!("{}", printer::tree(ast));
+    }
+    if output.shows_sexpr() {
+        println!("{}", printer::sexpr(ast));
     }
 }
 
-pub fn parse_interactive() -> () {
+pub fn parse_interactive(mut output: OutputMode, settings: Settings) {
     use std::io::{stdin, stdout, BufRead, BufReader, Write};
     let prompt = |s: &str| {
         let stdout = stdout();
         let mut stdout = stdout.lock();
-        stdout.write(s.as_bytes())?;
+        stdout.write_all(s.as_bytes())?;
         stdout.flush()
     };
     let stdin = stdin();
     // let mut stdin = stdin.lock();
     let stdin = BufReader::new(stdin.lock());
     let mut lines = stdin.lines();
+    let mut session = Session::with_settings(settings.clone());
     loop {
         prompt("> ").unwrap();
         if let Some(Ok(line)) = lines.next() {
-            match line.parse::<ast::Ast>() {
-                Ok(ast) => {
-                    println!("{:?}", ast);
-                    println!("result: {:?}", interpreter::eval(&ast))
-                }
-                Err(errors::CompileError::Lexer(lex_error)) => {
-                    println!("{}", line);
-                    let token::Loc { start, end } = lex_error.loc;
-                    println!(
-                        "{}{}{}",
-                        String::from(" ").repeat(start),
-                        String::from("^").repeat(end - start),
-                        lex_error
-                    );
+            if line.starts_with(':') {
+                run_command(&mut session, &mut output, &settings, &line);
+                continue;
+            }
+            if output.shows_tokens() {
+                if let Ok(tokens) = session.lex(&line) {
+                    print!("{}", printer::tokens(&tokens));
                 }
-                Err(errors::CompileError::Parser(parse_error)) => {
-                    let start;
-                    let end;
-                    match parse_error.token() {
-                        Some(token) => {
-                            start = token.loc.start;
-                            end = token.loc.end;
-                        }
-                        None => {
-                            start = line.len() - 1;
-                            end = line.len();
+            }
+            match session.parse(&line) {
+                Ok(ast) => {
+                    print_ast(&ast, output);
+                    match session.eval(&line, &ast) {
+                        Ok(value) => {
+                            println!("${} = {}", session.history().len(), session.display(&value))
                         }
+                        Err(interpreter_error) => report(&line, &interpreter_error.into()),
+                    }
+                }
+                Err(compile_errors) => {
+                    for compile_error in &compile_errors {
+                        report(&line, compile_error);
                     }
-                    println!("{}", line);
-                    println!(
-                        "{}{} {}",
-                        String::from(" ").repeat(start),
-                        String::from("^").repeat(end - start),
-                        parse_error
-                    );
                 }
-                _ => unreachable!(),
             }
         } else {
             break;
         }
     }
 }
+
+fn parse_or_report(session: &Session, source: &str) -> Option<parser::ast::Ast> {
+    match session.parse(source) {
+        Ok(ast) => Some(ast),
+        Err(compile_errors) => {
+            for compile_error in &compile_errors {
+                report(source, compile_error);
+            }
+            None
+        }
+    }
+}
+
+fn report(line: &str, error: &errors::CompileError) {
+    print!("{}", Diagnostic::from_error(error, line).render(line, None));
+}
+
+// :save <path> | :load <path> | :set [<name> <on|off|limit>]
+// | :tokens | :ast [tree|sexpr] | :quiet | :verbose | :rpn <expr> | :diff <variable> <expr>
+// | :type <expr>
+// `settings` are those the REPL started with, which a loaded session starts from
+fn run_command(session: &mut Session, output: &mut OutputMode, settings: &Settings, line: &str) {
+    if let Some(source) = line.strip_prefix(":type ") {
+        if let Some(ast) = parse_or_report(session, source) {
+            match session.check(&ast).value.ty {
+                Some(ty) => println!("{}", ty),
+                None => println!("unknown"),
+            }
+        }
+        return;
+    }
+    if let Some(source) = line.strip_prefix(":rpn ") {
+        if let Some(ast) = parse_or_report(session, source) {
+            match rpn::rpn(&ast) {
+                Ok(postfix) => println!("{}", postfix),
+                Err(e) => println!("{}", e),
+            }
+        }
+        return;
+    }
+    if let Some(args) = line.strip_prefix(":diff ") {
+        match args.trim_start().split_once(' ') {
+            Some((var, source)) => {
+                if let Some(ast) = parse_or_report(session, source) {
+                    match derive::derivative(&ast, var) {
+                        Ok(derivative) => println!("{}", derivative),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
+            None => println!("usage: :diff <variable> <expr>"),
+        }
+        return;
+    }
+    let args: Vec<&str> = line.split_whitespace().collect();
+    let mode = match args.as_slice() {
+        [":tokens"] => Some(OutputMode::Tokens),
+        [":ast"] | [":ast", "tree"] => Some(OutputMode::Tree),
+        [":ast", "sexpr"] => Some(OutputMode::SExpr),
+        [":quiet"] => Some(OutputMode::Value),
+        [":verbose"] => Some(OutputMode::Verbose),
+        _ => None,
+    };
+    if let Some(mode) = mode {
+        *output = mode;
+        return;
+    }
+    let result = match args.as_slice() {
+        [":save", path] => session.save(path),
+        [":load", path] => {
+            Session::load_with(path, settings.clone()).map(|loaded| *session = loaded)
+        }
+        [":set"] => {
+            println!("{}", session.settings());
+            Ok(())
+        }
+        [":set", name, value] => session.set(name, value),
+        _ => {
+            println!("unknown command: {}", line);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        println!("{}", e);
+    }
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+    let options = |input, output| {
+        Ok(Options {
+            input,
+            output,
+            format: Format::Text,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_tree_depth: DEFAULT_MAX_TREE_DEPTH,
+        })
+    };
+    assert_eq!(
+        args(&["-e", "1 + 2"]),
+        options(Input::Expr("1 + 2".to_string()), OutputMode::Value)
+    );
+    assert_eq!(
+        args(&["main.calc", "--output", "sexpr"]),
+        options(Input::File("main.calc".to_string()), OutputMode::SExpr)
+    );
+    assert_eq!(args(&["-"]), options(Input::Stdin, OutputMode::Value));
+    assert!(args(&["-e"]).is_err());
+    assert!(args(&["-x"]).is_err());
+    assert!(args(&["a", "b"]).is_err());
+    assert!(args(&["-o", "json", "-"]).is_err());
+    assert_eq!(
+        args(&["--format", "json", "-"]).map(|options| options.format),
+        Ok(Format::Json)
+    );
+    let limits = args(&["--depth", "500", "--length", "20000", "-"]);
+    assert_eq!(
+        limits.map(|options| (options.max_depth, options.max_tree_depth)),
+        Ok((500, 20_000))
+    );
+    assert!(args(&["--depth", "0", "-"]).is_err());
+    assert!(args(&["--length"]).is_err());
+}
@@ -0,0 +1,297 @@
+//! Rewrites a tree into a simpler one that evaluates to the same value:
+//!
+//! - subtrees of constants are evaluated, unless that fails: `1 / 0` stays as it is so
+//!   that evaluation still reports `DivisionByZero` at its location
+//! - `if` with a constant condition becomes the branch taken
+//! - `+x`, `x + 0`, `x - 0`, `x * 1`, `1 * x`, `x / 1` and `x ^ 1` become `x`, but `--x`
+//!   stays as it is, since `-x` overflows for the smallest integer
+//! - `x * 0` and `0 * x` are left as they are unless `x` is a constant: even a variable
+//!   may be unbound, and `0 * x` is `0.0` for a float
+//! - integer constants of `*` chains are gathered where that cannot change the result:
+//!   `(x * 2) * 3` becomes `x * 6`, but `(x * 3) * 5` and `(x + 1) + 2` stay as they are,
+//!   since a float `x` would be rounded once instead of twice
+//!
+//! The identities assume that variables hold numbers. A node built by the optimizer spans
+//! the source of the expression it replaces, so errors still point at the right place.
+use std::convert::TryFrom;
+
+use crate::{
+    ast::{Ast, AstKind, BinOperator, BinOperatorKind, UniOperator, UniOperatorKind},
+    interpreter::{self, EvalOptions},
+    token::{Float, Loc},
+    value::Value,
+    visit::{fold_ast, Fold},
+};
+
+pub fn optimize(ast: Ast) -> Ast {
+    optimize_with(ast, &EvalOptions::default())
+}
+
+/// `optimize` folding constants the way an evaluation with `options` would,
+/// e.g. `1 / 2` stays as it is in rational mode.
+pub fn optimize_with(ast: Ast, options: &EvalOptions) -> Ast {
+    Optimizer { options }.fold_ast(ast)
+}
+
+struct Optimizer<'a> {
+    options: &'a EvalOptions,
+}
+impl Fold for Optimizer<'_> {
+    fn fold_ast(&mut self, ast: Ast) -> Ast {
+        // children first, so that constants propagate upward
+        simplify(fold_ast(self, ast), self.options)
+    }
+}
+
+fn simplify(ast: Ast, options: &EvalOptions) -> Ast {
+    if constant(&ast).is_none() && children_are_constant(&ast) {
+        let mut env = interpreter::Environment::new();
+        if let Some(folded) = interpreter::eval_with_options(&ast, &mut env, options)
+            .ok()
+            .and_then(|value| to_ast(&value, &ast.loc))
+        {
+            return folded;
+        }
+        // the error is left for evaluation to report
+        return ast;
+    }
+    let loc = ast.loc;
+    match ast.value {
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => match cond.value {
+            AstKind::Bool(true) => *then_branch,
+            AstKind::Bool(false) => *else_branch,
+            _ => Ast::new(
+                AstKind::If {
+                    cond,
+                    then_branch,
+                    else_branch,
+                },
+                loc,
+            ),
+        },
+        AstKind::UniOperator { operator, tree } => simplify_uni_operator(operator, *tree, loc),
+        AstKind::BinOperator { operator, lhs, rhs } => {
+            simplify_bin_operator(operator, *lhs, *rhs, loc)
+        }
+        value => Ast::new(value, loc),
+    }
+}
+
+fn simplify_uni_operator(operator: UniOperator, tree: Ast, loc: Loc) -> Ast {
+    match (&operator.value, tree.value) {
+        (UniOperatorKind::Plus, value) if !is_boolean(&value) => Ast::new(value, tree.loc),
+        (_, value) => Ast::new(
+            AstKind::UniOperator {
+                operator,
+                tree: Box::new(Ast::new(value, tree.loc)),
+            },
+            loc,
+        ),
+    }
+}
+
+fn simplify_bin_operator(operator: BinOperator, lhs: Ast, rhs: Ast, loc: Loc) -> Ast {
+    use BinOperatorKind::*;
+    let (lhs, rhs) = match operator.value {
+        // constants to the right, so that `(2 * x) * 3` is gathered like `(x * 2) * 3`
+        Add | Mul if int(&lhs).is_some() && int(&rhs).is_none() => (rhs, lhs),
+        _ => (lhs, rhs),
+    };
+    match (&operator.value, int(&rhs)) {
+        (Add, Some(0)) | (Sub, Some(0)) | (Mul, Some(1)) | (Div, Some(1)) | (Pow, Some(1))
+            if !is_boolean(&lhs.value) =>
+        {
+            return lhs
+        }
+        // `x * c1` is exact for a power of two `c1`, so `(x * c1) * c2` rounds only once, like
+        // `x * (c1 * c2)`. For integers, the one overflows when the other does, unless `c2`
+        // is 0, or -1 with `x * c1` just out of range.
+        (Mul, Some(c2)) if c2 != 0 && c2 != -1 => {
+            if let AstKind::BinOperator {
+                operator: inner,
+                lhs: inner_lhs,
+                rhs: inner_rhs,
+            } = &lhs.value
+            {
+                let gathered = match int(inner_rhs) {
+                    Some(c1) if inner.value == Mul && c1.unsigned_abs().is_power_of_two() => {
+                        c1.checked_mul(c2)
+                    }
+                    _ => None,
+                };
+                let constant_loc = inner_rhs.loc.merge(&rhs.loc);
+                if let Some(constant) = gathered.and_then(|n| to_ast(&Value::Int(n), &constant_loc))
+                {
+                    let inner_lhs = (**inner_lhs).clone();
+                    return simplify_bin_operator(operator, inner_lhs, constant, loc);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ast::new(
+        AstKind::BinOperator {
+            operator,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+        loc,
+    )
+}
+
+// the value of a literal, or of a negated one as `to_ast` writes negative numbers
+fn constant(ast: &Ast) -> Option<Value> {
+    match &ast.value {
+        AstKind::Number(n) => i64::try_from(*n).ok().map(Value::Int),
+        AstKind::BigNumber(n) => Some(Value::BigInt(n.clone())),
+        AstKind::Float(Float(n)) => Some(Value::Float(*n)),
+        AstKind::Bool(b) => Some(Value::Bool(*b)),
+        AstKind::UniOperator { operator, tree } if operator.value == UniOperatorKind::Minus => {
+            match &tree.value {
+                AstKind::Number(n) if *n > 0 => i64::try_from(-(*n as i128)).ok().map(Value::Int),
+                AstKind::BigNumber(n) if !n.is_zero() => Some(Value::BigInt(-n)),
+                AstKind::Float(Float(n)) => Some(Value::Float(-*n)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn int(ast: &Ast) -> Option<i64> {
+    match constant(ast) {
+        Some(Value::Int(n)) => Some(n),
+        _ => None,
+    }
+}
+
+fn children_are_constant(ast: &Ast) -> bool {
+    match &ast.value {
+        AstKind::UniOperator { tree, .. } => constant(tree).is_some(),
+        AstKind::BinOperator { lhs, rhs, .. } => constant(lhs).is_some() && constant(rhs).is_some(),
+        _ => false,
+    }
+}
+
+fn to_ast(value: &Value, loc: &Loc) -> Option<Ast> {
+    let negate = |kind| AstKind::UniOperator {
+        operator: UniOperator::new(UniOperatorKind::Minus, loc.clone()),
+        tree: Box::new(Ast::new(kind, loc.clone())),
+    };
+    let kind = match *value {
+        Value::Int(n) if n >= 0 => AstKind::Number(n as u64),
+        Value::Int(n) => negate(AstKind::Number(n.unsigned_abs())),
+        Value::Float(n) if n.is_nan() => return None,
+        Value::Float(n) if n.is_sign_negative() => negate(AstKind::Float(Float(-n))),
+        Value::Float(n) => AstKind::Float(Float(n)),
+        Value::Rational(n) if n.is_integer() => return to_ast(&Value::Int(n.numerator()), loc),
+        // a fraction has no literal
+        Value::Rational(_) => return None,
+        Value::BigInt(ref n) => match n.to_i64() {
+            Some(n) => return to_ast(&Value::Int(n), loc),
+            None if n.is_negative() => negate(AstKind::BigNumber(n.abs())),
+            None => AstKind::BigNumber(n.clone()),
+        },
+        Value::Bool(b) => AstKind::Bool(b),
+        Value::Function(_) | Value::Builtin(_) => return None,
+    };
+    Some(Ast::new(kind, loc.clone()))
+}
+
+// whether `kind` certainly evaluates to a boolean, which `+` and the identities reject
+fn is_boolean(kind: &AstKind) -> bool {
+    match kind {
+        AstKind::Bool(_) => true,
+        AstKind::UniOperator { operator, .. } => operator.value == UniOperatorKind::Not,
+        AstKind::BinOperator { operator, .. } => !matches!(
+            operator.value,
+            BinOperatorKind::Add
+                | BinOperatorKind::Sub
+                | BinOperatorKind::Mul
+                | BinOperatorKind::Div
+                | BinOperatorKind::Mod
+                | BinOperatorKind::Pow
+                | BinOperatorKind::FloorDiv
+        ),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_optimize() {
+    let optimized = |source: &str| optimize(source.parse().unwrap());
+    let cases = [
+        ("1 + 2 * 3", "7"),
+        ("2 - 5", "-3"),
+        ("x * (2 - 1)", "x"),
+        ("+x + 0", "x"),
+        ("--x - 0", "--x"),
+        ("--5 + -(-2.5)", "7.5"),
+        ("0 * x + y", "x * 0 + y"),
+        ("(x + 1) + 2", "x + 1 + 2"),
+        ("2 * (x * 4) * -3", "x * -24"),
+        ("2 * (3 * x) * 4", "x * 3 * 8"),
+        ("x * 2 * 0 * -1", "x * 2 * 0 * -1"),
+        ("1 + (x + 2) * 1", "x + 2 + 1"),
+        ("x / 1 + y ^ 1", "x + y"),
+        ("1 / x + 1 ^ y", "1 / x + 1 ^ y"),
+        ("x ^ 0 - 0 - x", "x ^ 0 - x"),
+        ("if 1 < 2 then x * 1 else y", "x"),
+        ("1.5 * 2 - 0.5", "2.5"),
+        ("-2.0 * 1.5", "-3.0"),
+        ("!(1 > 2) && true", "true"),
+        ("+(1 > 2)", "+false"),
+        ("f(2 + 3)", "f(5)"),
+        // folding would lose the error
+        ("x + 1 / 0", "x + 1 / 0"),
+        ("(1 / 0) * 0", "1 / 0 * 0"),
+        ("9223372036854775807 + 1", "9223372036854775807 + 1"),
+    ];
+    for (source, expected) in cases.iter() {
+        assert_eq!(&optimized(source).to_string(), expected, "{}", source);
+    }
+
+    // folded nodes keep the span of what they replace
+    let ast = optimized("x + (1 / 0)");
+    assert_eq!(ast.loc, Loc::new(0, 10));
+    match ast.value {
+        AstKind::BinOperator { rhs, .. } => assert_eq!(rhs.loc, Loc::new(5, 10)),
+        _ => panic!("{:?}", ast),
+    }
+    let ast = optimized("(2 * 3) + x");
+    assert_eq!(ast.to_string(), "x + 6");
+    assert_eq!(ast.loc, Loc::new(1, 11));
+    let source = "0 * nope";
+    let error = interpreter::eval(&source.parse().unwrap()).unwrap_err();
+    assert_eq!(interpreter::eval(&optimized(source)), Err(error));
+    // the rewrites change neither rounding nor overflow
+    let at = |ast: &Ast, x: &Value| {
+        let mut env = interpreter::Environment::new();
+        env.set("x", x.clone());
+        interpreter::eval_with(ast, &mut env).map_err(|e| e.value)
+    };
+    let cases = [
+        ("x + 1 + -1", Value::Int(i64::MAX)),
+        ("x + 1 + 2", Value::Float(9007199254740992.0)),
+        ("x * 3 * 5", Value::Float(0.7062264574664955)),
+        ("x * 4 * -1", Value::Int(1 << 61)),
+        ("x * 2 * 3", Value::Float(0.1)),
+        ("x * -2 * 5", Value::Int(i64::MAX / 8)),
+        ("--x", Value::Int(i64::MIN)),
+        ("x / 1 * 3", Value::Float(0.1)),
+        ("x ^ 1 * 2", Value::Int(i64::MAX)),
+        ("x ^ 1 ^ 1", Value::Float(1.5)),
+    ];
+    for (source, x) in cases.iter() {
+        let ast: Ast = source.parse().unwrap();
+        assert_eq!(at(&optimized(source), x), at(&ast, x), "{}", source);
+    }
+
+    let source = "2 * 3 + 4 / (1 - 1)";
+    let error = interpreter::eval(&source.parse().unwrap()).unwrap_err();
+    assert_eq!(interpreter::eval(&optimized(source)), Err(error));
+}
@@ -0,0 +1,162 @@
+//! Formats an `Ast` back into source, with only the parentheses that precedence requires.
+//! The output parses back into the same tree, apart from locations.
+use crate::{
+    ast::{param_names, Ast, AstKind, BinOperatorKind},
+    token::Float,
+};
+
+// binding strength of each level of the grammar, from the loosest
+const EXPR: u8 = 0;
+const OR: u8 = 1;
+const AND: u8 = 2;
+const CMP: u8 = 3;
+const ADD: u8 = 4;
+const MUL: u8 = 5;
+const UNARY: u8 = 6;
+const POWER: u8 = 7;
+const CALL: u8 = 8;
+const ATOM: u8 = 9;
+
+pub fn pretty(ast: &Ast) -> String {
+    write(ast, EXPR)
+}
+
+impl std::fmt::Display for Ast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&pretty(self))
+    }
+}
+
+fn precedence(ast: &Ast) -> u8 {
+    match &ast.value {
+        AstKind::Number(_)
+        | AstKind::BigNumber(_)
+        | AstKind::Float(_)
+        | AstKind::Bool(_)
+        | AstKind::Variable(_)
+        | AstKind::Error => ATOM,
+        AstKind::Call { .. } => CALL,
+        AstKind::UniOperator { .. } => UNARY,
+        AstKind::BinOperator { operator, .. } => match operator.value {
+            BinOperatorKind::Or => OR,
+            BinOperatorKind::And => AND,
+            BinOperatorKind::Eq
+            | BinOperatorKind::Ne
+            | BinOperatorKind::Lt
+            | BinOperatorKind::Le
+            | BinOperatorKind::Gt
+            | BinOperatorKind::Ge => CMP,
+            BinOperatorKind::Add | BinOperatorKind::Sub => ADD,
+            BinOperatorKind::Mul
+            | BinOperatorKind::Div
+            | BinOperatorKind::FloorDiv
+            | BinOperatorKind::Mod => MUL,
+            BinOperatorKind::Pow => POWER,
+        },
+        // these extend as far right as possible
+        AstKind::Let { .. }
+        | AstKind::FnDef { .. }
+        | AstKind::Lambda { .. }
+        | AstKind::If { .. }
+        | AstKind::Program(_) => EXPR,
+    }
+}
+
+// `ast` in a position that needs at least `min` binding strength
+fn write(ast: &Ast, min: u8) -> String {
+    let source = match &ast.value {
+        AstKind::Number(n) => n.to_string(),
+        AstKind::BigNumber(n) => n.to_string(),
+        AstKind::Float(Float(n)) => format!("{:?}", n),
+        AstKind::Bool(b) => b.to_string(),
+        AstKind::Variable(name) => name.clone(),
+        AstKind::UniOperator { operator, tree } => {
+            format!("{}{}", operator.value, write(tree, UNARY))
+        }
+        AstKind::BinOperator { operator, lhs, rhs } => {
+            let level = precedence(ast);
+            let (lhs_min, rhs_min) = match level {
+                // `power = call ("^" unary)?`, right-associative
+                POWER => (CALL, UNARY),
+                // comparisons do not chain
+                CMP => (CMP + 1, CMP + 1),
+                _ => (level, level + 1),
+            };
+            format!(
+                "{} {} {}",
+                write(lhs, lhs_min),
+                operator.value,
+                write(rhs, rhs_min)
+            )
+        }
+        AstKind::Let { name, value } => format!("let {} = {}", name.value, write(value, EXPR)),
+        AstKind::FnDef { name, params, body } => format!(
+            "fn {}({}) = {}",
+            name.value,
+            param_names(params),
+            write(body, EXPR)
+        ),
+        AstKind::Lambda { params, body } => {
+            format!("fn({}) = {}", param_names(params), write(body, EXPR))
+        }
+        AstKind::Call { callee, args } => {
+            let args: Vec<String> = args.iter().map(|arg| write(arg, EXPR)).collect();
+            format!("{}({})", write(callee, CALL), args.join(", "))
+        }
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => format!(
+            "if {} then {} else {}",
+            write(cond, EXPR),
+            write(then_branch, EXPR),
+            write(else_branch, EXPR)
+        ),
+        AstKind::Program(stmts) => {
+            let stmts: Vec<String> = stmts.iter().map(|stmt| write(stmt, EXPR)).collect();
+            stmts.join("; ")
+        }
+        AstKind::Error => "<error>".to_string(),
+    };
+    if precedence(ast) < min {
+        format!("({})", source)
+    } else {
+        source
+    }
+}
+
+#[test]
+fn test_pretty() {
+    use crate::printer::sexpr;
+    let cases = [
+        ("((1+2))*3", "(1 + 2) * 3"),
+        ("1 - (2 - 3) - 4", "1 - (2 - 3) - 4"),
+        ("(1 - 2) - 3", "1 - 2 - 3"),
+        ("2 ^ (3 ^ 4)", "2 ^ 3 ^ 4"),
+        ("(2 ^ 3) ^ 4", "(2 ^ 3) ^ 4"),
+        ("-(2 ^ 2) + (-2) ** 2", "-2 ^ 2 + (-2) ^ 2"),
+        ("2 ^ -(1 + 1)", "2 ^ -(1 + 1)"),
+        ("!(a && b) || (c || d)", "!(a && b) || (c || d)"),
+        ("(1 < 2) == (3 >= 4)", "(1 < 2) == (3 >= 4)"),
+        (
+            "1 + (if a then 1 else 2) * 3",
+            "1 + (if a then 1 else 2) * 3",
+        ),
+        ("(fn(x) = x * 2)(1.5)", "(fn(x) = x * 2)(1.5)"),
+        ("fn f(x, y) = max(x, y)(0)", "fn f(x, y) = max(x, y)(0)"),
+        ("let x = 1e20; x // 2 % 3", "let x = 1e20; x // 2 % 3"),
+    ];
+    for (input, expected) in cases.iter() {
+        let ast: Ast = input.parse().unwrap();
+        let printed = pretty(&ast);
+        assert_eq!(&printed, expected);
+        let reparsed: Ast = printed.parse().unwrap();
+        assert_eq!(
+            sexpr(&reparsed),
+            sexpr(&ast),
+            "{} does not round-trip",
+            input
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    ast::{param_names, Ast, AstKind},
+    token::{Float, Token},
+};
+
+/// What the REPL and the CLI print for each input besides errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OutputMode {
+    /// the value only
+    #[default]
+    Value,
+    /// the token stream, then the value
+    Tokens,
+    /// the tree as an indented outline, then the value
+    Tree,
+    /// the tree as an S-expression, then the value
+    SExpr,
+    /// tokens, tree and value
+    Verbose,
+}
+impl std::str::FromStr for OutputMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "value" => Ok(OutputMode::Value),
+            "tokens" => Ok(OutputMode::Tokens),
+            "tree" => Ok(OutputMode::Tree),
+            "sexpr" => Ok(OutputMode::SExpr),
+            "verbose" => Ok(OutputMode::Verbose),
+            _ => Err(format!(
+                "unknown output mode '{}', expected value, tokens, tree, sexpr or verbose",
+                s
+            )),
+        }
+    }
+}
+impl OutputMode {
+    pub fn shows_tokens(self) -> bool {
+        matches!(self, OutputMode::Tokens | OutputMode::Verbose)
+    }
+    pub fn shows_tree(self) -> bool {
+        matches!(self, OutputMode::Tree | OutputMode::Verbose)
+    }
+    pub fn shows_sexpr(self) -> bool {
+        self == OutputMode::SExpr
+    }
+}
+
+/// One token per line with its location, e.g. `0-1 Number(1)`.
+pub fn tokens(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("{} {:?}\n", token.loc, token.value))
+        .collect()
+}
+
+/// The tree as an outline, one node per line and children indented under their parent.
+pub fn tree(ast: &Ast) -> String {
+    let mut out = String::new();
+    write_tree(ast, 0, &mut out);
+    out
+}
+
+fn write_tree(ast: &Ast, depth: usize, out: &mut String) {
+    let label = match &ast.value {
+        AstKind::Number(n) => format!("Number {}", n),
+        AstKind::BigNumber(n) => format!("BigNumber {}", n),
+        AstKind::Float(Float(n)) => format!("Float {:?}", n),
+        AstKind::Bool(b) => format!("Bool {}", b),
+        AstKind::UniOperator { operator, .. } => format!("UniOperator {}", operator.value),
+        AstKind::BinOperator { operator, .. } => format!("BinOperator {}", operator.value),
+        AstKind::Variable(name) => format!("Variable {}", name),
+        AstKind::Let { name, .. } => format!("Let {}", name.value),
+        AstKind::FnDef { name, params, .. } => {
+            format!("FnDef {}({})", name.value, param_names(params))
+        }
+        AstKind::Lambda { params, .. } => format!("Lambda ({})", param_names(params)),
+        AstKind::Call { .. } => "Call".to_string(),
+        AstKind::If { .. } => "If".to_string(),
+        AstKind::Program(_) => "Program".to_string(),
+        AstKind::Error => "Error".to_string(),
+    };
+    *out += &format!("{}{} @{}\n", "  ".repeat(depth), label, ast.loc);
+    for child in children(ast) {
+        write_tree(child, depth + 1, out);
+    }
+}
+
+/// The tree as an S-expression, e.g. `(+ 1 (* 2 3))`.
+pub fn sexpr(ast: &Ast) -> String {
+    let list = |head: String, rest: Vec<&Ast>| {
+        let items: Vec<String> = std::iter::once(head)
+            .chain(rest.into_iter().map(sexpr))
+            .collect();
+        format!("({})", items.join(" "))
+    };
+    match &ast.value {
+        AstKind::Number(n) => n.to_string(),
+        AstKind::BigNumber(n) => n.to_string(),
+        AstKind::Float(Float(n)) => format!("{:?}", n),
+        AstKind::Bool(b) => b.to_string(),
+        AstKind::Variable(name) => name.clone(),
+        AstKind::UniOperator { operator, .. } => list(operator.value.to_string(), children(ast)),
+        AstKind::BinOperator { operator, .. } => list(operator.value.to_string(), children(ast)),
+        AstKind::Let { name, .. } => list(format!("let {}", name.value), children(ast)),
+        AstKind::FnDef { name, params, .. } => list(
+            format!("fn {} ({})", name.value, params_sexpr(params)),
+            children(ast),
+        ),
+        AstKind::Lambda { params, .. } => {
+            list(format!("fn ({})", params_sexpr(params)), children(ast))
+        }
+        AstKind::Call { .. } => list("call".to_string(), children(ast)),
+        AstKind::If { .. } => list("if".to_string(), children(ast)),
+        AstKind::Program(_) => list("do".to_string(), children(ast)),
+        AstKind::Error => "(error)".to_string(),
+    }
+}
+
+fn params_sexpr(params: &[crate::ast::Identifier]) -> String {
+    param_names(params).replace(',', "")
+}
+
+fn children(ast: &Ast) -> Vec<&Ast> {
+    match &ast.value {
+        AstKind::Number(_)
+        | AstKind::BigNumber(_)
+        | AstKind::Float(_)
+        | AstKind::Bool(_)
+        | AstKind::Variable(_)
+        | AstKind::Error => vec![],
+        AstKind::UniOperator { tree, .. } => vec![tree],
+        AstKind::BinOperator { lhs, rhs, .. } => vec![lhs, rhs],
+        AstKind::Let { value, .. } => vec![value],
+        AstKind::FnDef { body, .. } | AstKind::Lambda { body, .. } => vec![body],
+        AstKind::Call { callee, args } => std::iter::once(&**callee).chain(args).collect(),
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => vec![cond, then_branch, else_branch],
+        AstKind::Program(stmts) => stmts.iter().collect(),
+    }
+}
+
+#[test]
+fn test_printers() {
+    let ast: Ast = "fn f(x, y) = -x + y * 2".parse().unwrap();
+    assert_eq!(sexpr(&ast), "(fn f (x y) (+ (- x) (* y 2)))");
+    assert_eq!(
+        tree(&ast),
+        "FnDef f(x, y) @0-23
+  BinOperator + @13-23
+    UniOperator - @13-15
+      Variable x @14-15
+    BinOperator * @18-23
+      Variable y @18-19
+      Number 2 @22-23
+"
+    );
+    let ast: Ast = "if f(1) then 2.5 else 3; true".parse().unwrap();
+    assert_eq!(sexpr(&ast), "(do (if (call f 1) 2.5 3) true)");
+}
@@ -0,0 +1,239 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use crate::interpreter::{InterpreterErrorKind, NumberKind};
+
+/// Exact fraction of two `i64`s.
+///
+/// Always in lowest terms with a positive denominator, so that derived equality
+/// and hashing are structural and `2/4` equals `1/2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+type RationalResult = Result<Rational, InterpreterErrorKind>;
+
+impl Rational {
+    pub fn new(numerator: i64, denominator: i64) -> RationalResult {
+        Self::reduce(numerator as i128, denominator as i128)
+    }
+
+    pub fn numerator(self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(self) -> i64 {
+        self.denominator
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.denominator == 1
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    // intermediate results are exact in `i128`; only the reduced fraction has to fit in `i64`
+    fn reduce(numerator: i128, denominator: i128) -> RationalResult {
+        if denominator == 0 {
+            return Err(InterpreterErrorKind::DivisionByZero);
+        }
+        let sign = if denominator < 0 { -1 } else { 1 };
+        let divisor = gcd(numerator, denominator) * sign;
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        match (i64::try_from(numerator), i64::try_from(denominator)) {
+            (Ok(numerator), Ok(denominator)) => Ok(Self {
+                numerator,
+                denominator,
+            }),
+            _ if numerator < 0 => Err(InterpreterErrorKind::Underflow(NumberKind::Rational)),
+            _ => Err(InterpreterErrorKind::Overflow(NumberKind::Rational)),
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> RationalResult {
+        let (a, b, c, d) = self.widen(other);
+        Self::reduce(a * d + c * b, b * d)
+    }
+
+    pub fn checked_sub(self, other: Self) -> RationalResult {
+        let (a, b, c, d) = self.widen(other);
+        Self::reduce(a * d - c * b, b * d)
+    }
+
+    pub fn checked_mul(self, other: Self) -> RationalResult {
+        let (a, b, c, d) = self.widen(other);
+        Self::reduce(a * c, b * d)
+    }
+
+    pub fn checked_div(self, other: Self) -> RationalResult {
+        let (a, b, c, d) = self.widen(other);
+        Self::reduce(a * d, b * c)
+    }
+
+    pub fn checked_neg(self) -> RationalResult {
+        Self::reduce(-(self.numerator as i128), self.denominator as i128)
+    }
+
+    /// The largest integer not greater than `self`.
+    pub fn floor(self) -> Self {
+        Self {
+            numerator: self.numerator.div_euclid(self.denominator),
+            denominator: 1,
+        }
+    }
+
+    /// The smallest integer not less than `self`.
+    pub fn ceil(self) -> Self {
+        // no larger in magnitude than the numerator, so it fits even for `i64::MIN`
+        let rounded_up = self.numerator.rem_euclid(self.denominator) != 0;
+        Self {
+            numerator: self.numerator.div_euclid(self.denominator) + rounded_up as i64,
+            denominator: 1,
+        }
+    }
+
+    /// The nearest integer, halves rounded away from zero as `f64::round` does.
+    pub fn round(self) -> Self {
+        let (numerator, denominator) = (self.numerator as i128, self.denominator as i128);
+        let rounded = (2 * numerator.abs() + denominator) / (2 * denominator);
+        Self {
+            numerator: (rounded * numerator.signum()) as i64,
+            denominator: 1,
+        }
+    }
+
+    pub fn checked_pow(self, exponent: i64) -> RationalResult {
+        let base = if exponent < 0 {
+            Self::from(1).checked_div(self)?
+        } else {
+            self
+        };
+        // the only bases whose powers do not overflow within 64 multiplications
+        match (base.numerator, base.denominator) {
+            (0, 1) | (1, 1) if exponent != 0 => return Ok(base),
+            (-1, 1) if exponent % 2 != 0 => return Ok(base),
+            (-1, 1) => return Ok(Self::from(1)),
+            _ => {}
+        }
+        let mut result = Self::from(1);
+        for _ in 0..exponent.unsigned_abs() {
+            result = result.checked_mul(base)?;
+        }
+        Ok(result)
+    }
+
+    /// Decimal expansion with at most `places` digits after the point, ending with `...`
+    /// when it is cut short, e.g. `0.333...` for `1/3` with 3 places.
+    pub fn to_decimal(self, places: usize) -> String {
+        let (numerator, denominator) = (self.numerator as i128, self.denominator as i128);
+        let sign = if numerator < 0 { "-" } else { "" };
+        let mut remainder = numerator.abs() % denominator;
+        let mut decimal = format!("{}{}", sign, numerator.abs() / denominator);
+        if remainder == 0 {
+            return decimal;
+        }
+        decimal.push('.');
+        for _ in 0..places {
+            remainder *= 10;
+            decimal.push(char::from(b'0' + (remainder / denominator) as u8));
+            remainder %= denominator;
+            if remainder == 0 {
+                return decimal;
+            }
+        }
+        decimal + "..."
+    }
+
+    fn widen(self, other: Self) -> (i128, i128, i128, i128) {
+        (
+            self.numerator as i128,
+            self.denominator as i128,
+            other.numerator as i128,
+            other.denominator as i128,
+        )
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(num: i64) -> Self {
+        Self {
+            numerator: num,
+            denominator: 1,
+        }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, c, d) = self.widen(*other);
+        (a * d).cmp(&(c * b))
+    }
+}
+
+/// Fractions as `numerator/denominator`, integers without a denominator.
+impl std::fmt::Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        let rem = a % b;
+        a = b;
+        b = rem;
+    }
+    a.abs()
+}
+
+#[test]
+fn test_rational() {
+    let r = |n, d| Rational::new(n, d).unwrap();
+    assert_eq!(r(2, -4), r(-1, 2));
+    assert_eq!(r(2, -4).to_string(), "-1/2");
+    assert_eq!(r(6, 3).to_string(), "2");
+    let third = r(1, 3);
+    let sum = third
+        .checked_add(third)
+        .unwrap()
+        .checked_add(third)
+        .unwrap();
+    assert_eq!(sum, Rational::from(1));
+    assert_eq!(r(1, 2).checked_sub(third), Ok(r(1, 6)));
+    assert_eq!(r(3, 4).checked_div(r(3, 2)), Ok(r(1, 2)));
+    assert_eq!(
+        third.checked_div(r(0, 1)),
+        Err(InterpreterErrorKind::DivisionByZero)
+    );
+    assert_eq!(r(2, 3).checked_pow(-3), Ok(r(27, 8)));
+    assert_eq!(r(-1, 1).checked_pow(i64::MAX), Ok(r(-1, 1)));
+    assert_eq!(r(-7, 2).floor(), r(-4, 1));
+    assert_eq!(r(-7, 2).ceil(), r(-3, 1));
+    assert_eq!(r(7, 2).ceil(), r(4, 1));
+    assert_eq!(r(i64::MIN, 1).ceil(), r(i64::MIN, 1));
+    assert_eq!(r(i64::MIN + 1, 2).ceil(), r(i64::MIN / 2 + 1, 1));
+    assert_eq!(r(i64::MIN, 1).floor(), r(i64::MIN, 1));
+    assert_eq!(r(i64::MIN, 1).round(), r(i64::MIN, 1));
+    assert_eq!(r(-7, 2).round(), r(-4, 1));
+    assert_eq!(r(7, 3).round(), r(2, 1));
+    assert!(r(1, 3) < r(1, 2));
+    assert_eq!(
+        r(i64::MAX, 1).checked_add(Rational::from(1)),
+        Err(InterpreterErrorKind::Overflow(NumberKind::Rational))
+    );
+    assert_eq!(r(1, 8).to_decimal(10), "0.125");
+    assert_eq!(r(-2, 3).to_decimal(4), "-0.6666...");
+}
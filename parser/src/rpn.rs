@@ -0,0 +1,146 @@
+//! Writes an `Ast` as the postfix notation read by the `rpn` evaluator of this repository:
+//! numbers and the operators `+ - * /`, separated by whitespace.
+//!
+//! `rpn` has no unary operators, so `-x` becomes `x -1 *`, or a negative number when `x` is a
+//! literal, and `+x` becomes `x`. It computes with `f64`, so `/` only agrees with the parser's
+//! integer division when the division is exact.
+use crate::{
+    ast::{Ast, AstKind, BinOperatorKind, UniOperatorKind},
+    token::{Annotation, Float},
+};
+
+/// A part of the tree that `rpn` has no token for, described as e.g. `operator ^`.
+pub type RpnError = Annotation<String>;
+
+impl std::fmt::Display for RpnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} cannot be written in RPN", self.loc, self.value)
+    }
+}
+
+pub fn rpn(ast: &Ast) -> Result<String, RpnError> {
+    let mut tokens = vec![];
+    write(ast, &mut tokens)?;
+    Ok(tokens.join(" "))
+}
+
+fn write(ast: &Ast, tokens: &mut Vec<String>) -> Result<(), RpnError> {
+    let unsupported = |what: String| Err(RpnError::new(what, ast.loc.clone()));
+    match &ast.value {
+        AstKind::Number(_) | AstKind::BigNumber(_) | AstKind::Float(_) => {
+            tokens.push(number(&ast.value).expect("a number"))
+        }
+        AstKind::UniOperator { operator, tree } => match operator.value {
+            UniOperatorKind::Plus => write(tree, tokens)?,
+            UniOperatorKind::Minus => match number(&tree.value) {
+                Some(number) => tokens.push(format!("-{}", number)),
+                None => {
+                    write(tree, tokens)?;
+                    tokens.push("-1".to_string());
+                    tokens.push("*".to_string());
+                }
+            },
+            UniOperatorKind::Not => return unsupported(format!("operator {}", operator.value)),
+        },
+        AstKind::BinOperator { operator, lhs, rhs } => match operator.value {
+            BinOperatorKind::Add
+            | BinOperatorKind::Sub
+            | BinOperatorKind::Mul
+            | BinOperatorKind::Div => {
+                write(lhs, tokens)?;
+                write(rhs, tokens)?;
+                tokens.push(operator.value.to_string());
+            }
+            _ => return unsupported(format!("operator {}", operator.value)),
+        },
+        AstKind::Bool(b) => return unsupported(format!("boolean {}", b)),
+        AstKind::Variable(name) => return unsupported(format!("variable {}", name)),
+        AstKind::Let { .. } => return unsupported("let".to_string()),
+        AstKind::FnDef { .. } | AstKind::Lambda { .. } => {
+            return unsupported("function".to_string())
+        }
+        AstKind::Call { .. } => return unsupported("function call".to_string()),
+        AstKind::If { .. } => return unsupported("if".to_string()),
+        AstKind::Program(_) => return unsupported("sequence".to_string()),
+        AstKind::Error => return unsupported("syntax error".to_string()),
+    }
+    Ok(())
+}
+
+// a literal as a token that `str::parse::<f64>` reads back
+fn number(kind: &AstKind) -> Option<String> {
+    match kind {
+        AstKind::Number(n) => Some(n.to_string()),
+        AstKind::BigNumber(n) => Some(n.to_string()),
+        AstKind::Float(Float(n)) => Some(format!("{:?}", n)),
+        _ => None,
+    }
+}
+
+// the stack evaluator of the `rpn` binary, which is not a library, without its tracing
+#[cfg(test)]
+mod evaluator {
+    pub(super) fn eval(exp: &str) -> f64 {
+        let mut stack = Vec::new();
+        for token in exp.split_whitespace() {
+            if let Ok(num) = token.parse::<f64>() {
+                stack.push(num);
+                continue;
+            }
+            let (y, x) = (stack.pop(), stack.pop());
+            let (x, y) = x.zip(y).expect("Stack underflow");
+            stack.push(match token {
+                "+" => x + y,
+                "-" => x - y,
+                "*" => x * y,
+                "/" => x / y,
+                _ => panic!("Unknown operator: {}", token),
+            });
+        }
+        stack.pop().expect("Stack underflow")
+    }
+}
+
+#[test]
+fn test_rpn() {
+    use crate::{interpreter, token::Loc};
+
+    let cases = [
+        (
+            "6.1 + 5.2 * 4.3 - 3.4 / 2.5 * 1.6",
+            "6.1 5.2 4.3 * + 3.4 2.5 / 1.6 * -",
+        ),
+        ("(1 + 2) * 3", "1 2 + 3 *"),
+        ("1 - (2 - 3)", "1 2 3 - -"),
+        ("-(1 + 2) * -4", "1 2 + -1 * -4 *"),
+        ("+2.5 - -1e20", "2.5 -1e20 -"),
+        ("--7 / 2.0", "-7 -1 * 2.0 /"),
+        ("9223372036854775808 * 2", "9223372036854775808 2 *"),
+    ];
+    for (source, expected) in cases.iter() {
+        let ast: Ast = source.parse().unwrap();
+        let postfix = rpn(&ast).unwrap();
+        assert_eq!(&postfix, expected);
+        // the same formula through both engines
+        if let Ok(value) = interpreter::eval(&ast) {
+            let expected = value.as_f64().unwrap();
+            let found = evaluator::eval(&postfix);
+            assert!(
+                (found - expected).abs() <= 1e-9 * expected.abs(),
+                "{}: {} != {}",
+                source,
+                found,
+                expected
+            );
+        }
+    }
+    let error = rpn(&"1 + 2 ^ x".parse().unwrap()).unwrap_err();
+    assert_eq!(
+        error,
+        RpnError::new("operator ^".to_string(), Loc::new(4, 9))
+    );
+    assert_eq!(
+        error.to_string(),
+        "4-9: operator ^ cannot be written in RPN"
+    );
+}
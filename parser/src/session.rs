@@ -0,0 +1,380 @@
+use crate::{
+    ast::{self, Ast, ParseOptions},
+    bytecode,
+    errors::CompileError,
+    interpreter::{self, Environment, EvalOptions, InterpreterError, NumberMode},
+    optimize,
+    token::{self, LexError, LexOptions, Token},
+    typecheck::{self, TypedAst},
+    value::Value,
+};
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// accept integer literals of any size and evaluate integers exactly, unless `rational` is set
+    pub big_integers: bool,
+    /// simplify each tree with `optimize::optimize` before evaluating it
+    pub optimize: bool,
+    /// evaluate with `bytecode` instead of `interpreter`
+    pub vm: bool,
+    /// evaluate integer literals as exact fractions, so that `1 / 3 * 3` is `1`
+    pub rational: bool,
+    /// display fractions as decimals, e.g. `0.333...` instead of `1/3`
+    pub decimals: bool,
+    /// `EvalOptions::max_depth`, which only the interpreter needs: `bytecode` does not
+    /// recurse on the native stack
+    pub max_depth: usize,
+    /// `ParseOptions::max_tree_depth`, which bounds the length of chains like `1 + 2 + 3`
+    pub max_tree_depth: usize,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            big_integers: false,
+            optimize: false,
+            vm: false,
+            rational: false,
+            decimals: false,
+            max_depth: EvalOptions::default().max_depth,
+            max_tree_depth: ParseOptions::default().max_tree_depth,
+        }
+    }
+}
+impl Settings {
+    /// Sets a flag to `on` or `off`, or a limit to a number. Raising a limit takes as much
+    /// more native stack, so a thread with the default stack may overflow it.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SessionError> {
+        let limit = match name {
+            "depth" => Some(&mut self.max_depth),
+            "length" => Some(&mut self.max_tree_depth),
+            _ => None,
+        };
+        if let Some(limit) = limit {
+            *limit = match value.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(SessionError::InvalidLimit(value.to_string())),
+            };
+            return Ok(());
+        }
+        let flag = match value {
+            "on" => true,
+            "off" => false,
+            _ => return Err(SessionError::InvalidSettingValue(value.to_string())),
+        };
+        match name {
+            "bigint" => self.big_integers = flag,
+            "optimize" => self.optimize = flag,
+            "vm" => self.vm = flag,
+            "rational" => self.rational = flag,
+            "decimals" => self.decimals = flag,
+            _ => return Err(SessionError::UnknownSetting(name.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn lex_options(&self) -> LexOptions {
+        LexOptions {
+            big_integers: self.big_integers,
+        }
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            max_tree_depth: self.max_tree_depth,
+            ..ParseOptions::default()
+        }
+    }
+
+    pub fn eval_options(&self) -> EvalOptions {
+        EvalOptions {
+            numbers: if self.rational {
+                NumberMode::Rational
+            } else if self.big_integers {
+                NumberMode::BigInt
+            } else {
+                NumberMode::Machine
+            },
+            max_depth: self.max_depth,
+            ..EvalOptions::default()
+        }
+    }
+}
+impl std::fmt::Display for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let on_off = |flag: bool| if flag { "on" } else { "off" };
+        write!(
+            f,
+            "bigint {}\noptimize {}\nvm {}\nrational {}\ndecimals {}\ndepth {}\nlength {}",
+            on_off(self.big_integers),
+            on_off(self.optimize),
+            on_off(self.vm),
+            on_off(self.rational),
+            on_off(self.decimals),
+            self.max_depth,
+            self.max_tree_depth
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    UnknownSetting(String),
+    InvalidSettingValue(String),
+    InvalidLimit(String),
+    Io(std::io::Error),
+    /// a line of a saved session failed to replay (line numbers start at 1)
+    Load(usize, CompileError),
+}
+impl From<std::io::Error> for SessionError {
+    fn from(e: std::io::Error) -> Self {
+        SessionError::Io(e)
+    }
+}
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::UnknownSetting(name) => write!(f, "unknown setting '{}'", name),
+            SessionError::InvalidSettingValue(value) => {
+                write!(f, "invalid setting value '{}', expected on or off", value)
+            }
+            SessionError::InvalidLimit(value) => {
+                write!(f, "invalid limit '{}', expected a positive number", value)
+            }
+            SessionError::Io(e) => write!(f, "{}", e),
+            SessionError::Load(line, e) => write!(f, "line {}: {}", line, e),
+        }
+    }
+}
+
+/// State carried over between the lines of an interactive session:
+/// bindings, evaluated results and settings.
+///
+/// Results are bound as `$1`, `$2`, ... in evaluation order and the latest one as `_`.
+/// A session is saved as the transcript of the lines that changed it, and loaded by replaying them.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    env: Environment,
+    history: Vec<Value>,
+    settings: Settings,
+    transcript: Vec<String>,
+}
+
+const SET_COMMAND: &str = ":set";
+// digits after the point when displaying fractions as decimals
+const DECIMAL_PLACES: usize = 20;
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_settings(settings: Settings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    pub fn history(&self) -> &[Value] {
+        &self.history
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn lex(&self, line: &str) -> Result<Vec<Token>, LexError> {
+        token::lex_with(line, &self.settings.lex_options())
+    }
+
+    /// Parses and type checks `line`, reporting every syntax error in it, or else every
+    /// type error.
+    pub fn parse(&self, line: &str) -> Result<Ast, Vec<CompileError>> {
+        let tokens = self.lex(line).map_err(|e| vec![e.into()])?;
+        let (ast, errors) = ast::parse_recovering_with(tokens, &self.settings.parse_options());
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(CompileError::from).collect());
+        }
+        let (_, errors) =
+            typecheck::check_with_options(&ast, &self.env, &self.settings.parse_options());
+        if !errors.is_empty() {
+            Err(errors.into_iter().map(CompileError::from).collect())
+        } else if self.settings.optimize {
+            Ok(optimize::optimize_with(ast, &self.settings.eval_options()))
+        } else {
+            Ok(ast)
+        }
+    }
+
+    /// The types of `ast` with the bindings of the session in scope.
+    pub fn check(&self, ast: &Ast) -> TypedAst {
+        typecheck::check_with_options(ast, &self.env, &self.settings.parse_options()).0
+    }
+
+    /// Evaluates `ast` parsed from `line`, recording the result in the history.
+    pub fn eval(&mut self, line: &str, ast: &Ast) -> Result<Value, InterpreterError> {
+        let options = self.settings.eval_options();
+        let value = if self.settings.vm {
+            bytecode::eval_with_options(ast, &mut self.env, &options)?
+        } else {
+            interpreter::eval_with_options(ast, &mut self.env, &options)?
+        };
+        self.history.push(value.clone());
+        self.env.set("_", value.clone());
+        self.env
+            .set(&format!("${}", self.history.len()), value.clone());
+        self.transcript.push(line.to_string());
+        Ok(value)
+    }
+
+    /// `value` as the settings ask to display it.
+    pub fn display(&self, value: &Value) -> String {
+        match value {
+            Value::Rational(num) if self.settings.decimals => num.to_decimal(DECIMAL_PLACES),
+            value => value.to_string(),
+        }
+    }
+
+    pub fn run(&mut self, line: &str) -> Result<Value, CompileError> {
+        let ast = self.parse(line).map_err(|mut errors| errors.remove(0))?;
+        let value = self.eval(line, &ast)?;
+        Ok(value)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SessionError> {
+        self.settings.set(name, value)?;
+        self.transcript
+            .push(format!("{} {} {}", SET_COMMAND, name, value));
+        Ok(())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), SessionError> {
+        let mut contents = String::from("# parser session\n");
+        for line in &self.transcript {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, SessionError> {
+        Self::load_with(path, Settings::default())
+    }
+
+    /// `load` starting from `settings` rather than the defaults.
+    pub fn load_with(path: &str, settings: Settings) -> Result<Self, SessionError> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut session = Self::with_settings(settings);
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix(SET_COMMAND) {
+                Some(args) => {
+                    let mut args = args.split_whitespace();
+                    let name = args.next().unwrap_or_default();
+                    let value = args.next().unwrap_or_default();
+                    session.set(name, value)?;
+                }
+                None => {
+                    session
+                        .run(line)
+                        .map_err(|e| SessionError::Load(index + 1, e))?;
+                }
+            }
+        }
+        Ok(session)
+    }
+}
+
+#[test]
+fn test_session_history_and_save() {
+    let mut session = Session::new();
+    assert_eq!(session.run("let x = 3"), Ok(Value::Int(3)));
+    assert_eq!(session.run("x * 2"), Ok(Value::Int(6)));
+    // type errors are found before evaluation, which would stop at `1 / 0`
+    let errors = session.parse("1 / 0 + (x && true)").unwrap_err();
+    let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "9-10: expected bool but found number",
+            "9-18: expected number but found bool"
+        ]
+    );
+    assert_eq!(session.run("_ + $1"), Ok(Value::Int(9)));
+    session.set("bigint", "on").unwrap();
+    assert!(session.run("1 + $4").is_err());
+    let power = "1606938044258990275541962092341162602522202993782792835301376";
+    assert_eq!(
+        session.run("2 ^ 200").map(|v| v.to_string()),
+        Ok(power.to_string())
+    );
+
+    let path = std::env::temp_dir().join(format!("parser-session-{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    session.save(path).unwrap();
+    let mut loaded = Session::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(loaded.history(), session.history());
+    assert!(loaded.settings().big_integers);
+    assert_eq!(loaded.run("x + $3"), Ok(Value::Int(12)));
+    loaded.set("vm", "on").unwrap();
+    assert_eq!(loaded.run("fn f(y) = x * y; f($5)"), Ok(Value::Int(36)));
+    let product = loaded.run("fn g(y) = y * 2 ^ 100; g(2 ^ 100)");
+    assert_eq!(product.map(|v| v.to_string()), Ok(power.to_string()));
+}
+
+#[test]
+fn test_session_rational() {
+    use crate::rational::Rational;
+
+    let mut session = Session::new();
+    assert_eq!(session.run("1 / 3 + 1 / 3 + 1 / 3"), Ok(Value::Int(0)));
+    session.set("rational", "on").unwrap();
+    let one = Value::Rational(Rational::from(1));
+    assert_eq!(session.run("1 / 3 + 1 / 3 + 1 / 3"), Ok(one.clone()));
+    assert_eq!(
+        session.run("1 / 3 + 1 / 3 + 1 / 3 == 1"),
+        Ok(Value::Bool(true))
+    );
+    let two_thirds = session.run("2 / 3").unwrap();
+    assert_eq!(session.display(&two_thirds), "2/3");
+    session.set("decimals", "on").unwrap();
+    assert_eq!(session.display(&two_thirds), "0.66666666666666666666...");
+    let eighth = session.run("1 / 8").unwrap();
+    assert_eq!(session.display(&eighth), "0.125");
+    session.set("vm", "on").unwrap();
+    session.set("optimize", "on").unwrap();
+    assert_eq!(session.run("fn f(n) = n / 3; f(1) + f(2)"), Ok(one));
+}
+
+#[test]
+fn test_session_long_input() {
+    // rejected by the parser before anything walks the tree recursively
+    let sum = format!("1{}", " + 1".repeat(5_000));
+    let errors = Session::new().parse(&sum).unwrap_err();
+    let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(errors, ["4002-4003: Plus makes the expression too long"]);
+
+    // the limits are settings
+    let mut session = Session::new();
+    session.set("depth", "3").unwrap();
+    let count = "fn count(n) = if n == 0 then 0 else count(n - 1); count(2)";
+    assert_eq!(session.run(count), Ok(Value::Int(0)));
+    assert!(session.run(&count.replace("(2)", "(3)")).is_err());
+    session.set("vm", "on").unwrap();
+    assert_eq!(
+        session.run(&count.replace("(2)", "(100)")),
+        Ok(Value::Int(0))
+    );
+    assert!(session.set("depth", "none").is_err());
+    session.set("length", "2").unwrap();
+    assert!(session.run("1 + 2 + 3").is_ok());
+    assert!(session.run("1 + 2 + 3 + 4").is_err());
+    assert!(session
+        .settings()
+        .to_string()
+        .ends_with("depth 3\nlength 2"));
+}
@@ -0,0 +1,95 @@
+use crate::token::Loc;
+
+/// Line and column of a byte offset, both starting at 0.
+/// Columns count chars, so they line up under non-ASCII text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+/// Writes `line:column` counting from 1, as editors do.
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
+/// Maps the byte offsets in `Loc`s back to lines and columns of the source.
+#[derive(Debug, Clone)]
+pub struct SourceMap<'a> {
+    source: &'a str,
+    /// byte offset where each line starts
+    line_starts: Vec<usize>,
+}
+impl<'a> SourceMap<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Text of line `index` without its line break.
+    pub fn line(&self, index: usize) -> &'a str {
+        let start = self.line_starts[index];
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .map_or(self.source.len(), |next| next - 1);
+        self.source[start..end].trim_end_matches('\r')
+    }
+
+    /// Position of byte `offset`; offsets past the end map to the end of the last line.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = self.floor_char_boundary(offset);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let column = self.source[self.line_starts[line]..offset].chars().count();
+        Position { line, column }
+    }
+
+    /// Number of chars `loc` covers on the line where it starts.
+    pub fn width(&self, loc: &Loc) -> usize {
+        let start = self.position(loc.start);
+        let line_end = self.line_starts[start.line] + self.line(start.line).len();
+        let end = self.floor_char_boundary(std::cmp::min(loc.end, line_end));
+        let start = self.floor_char_boundary(loc.start);
+        self.source[start..std::cmp::max(start, end)]
+            .chars()
+            .count()
+    }
+
+    fn floor_char_boundary(&self, offset: usize) -> usize {
+        let mut offset = std::cmp::min(offset, self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+#[test]
+fn test_source_map() {
+    let map = SourceMap::new("let π = 3.14\nπ * r ^ 2\n");
+    assert_eq!(map.line_count(), 3);
+    assert_eq!(map.line(1), "π * r ^ 2");
+    assert_eq!(map.position(0), Position { line: 0, column: 0 });
+    // `=` follows the two-byte `π`
+    assert_eq!(map.position(7), Position { line: 0, column: 6 });
+    assert_eq!(map.position(17).to_string(), "2:3");
+    assert_eq!(map.width(&Loc::new(14, 16)), 1);
+    assert_eq!(map.position(100), Position { line: 2, column: 0 });
+}
@@ -1,3 +1,5 @@
+use crate::bigint::BigInt;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Loc {
     pub start: usize,
@@ -33,18 +35,108 @@ impl<T> Annotation<T> {
     }
 }
 
+/// `f64` wrapper so that float literals can live in `Eq + Hash` tokens and trees.
+/// Equality and hashing compare the bit pattern.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+impl Eq for Float {}
+impl std::hash::Hash for Float {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TokenKind {
     Number(u64),
+    /// integer literal too large for `u64`, only produced when `LexOptions::big_integers` is set
+    BigNumber(BigInt),
+    Float(Float),
     Plus,
     Minus,
     Asterisk,
     Slash,
+    Percent,
+    Caret,
+    /// `**`, same as `^`
+    DoubleAsterisk,
+    DoubleSlash,
     LParen,
     RParen,
+    Equal,
+    Comma,
+    /// `;`, separates statements
+    Semicolon,
+    /// line break, separates statements where it cannot continue the current one
+    Newline,
+    EqualEqual,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
+    Bang,
+    Ident(String),
+    Let,
+    Fn,
+    True,
+    False,
+    If,
+    Then,
+    Else,
 }
 pub type Token = Annotation<TokenKind>;
 
+/// Writes the token as it appears in the source.
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            TokenKind::Number(n) => return write!(f, "{}", n),
+            TokenKind::BigNumber(n) => return write!(f, "{}", n),
+            TokenKind::Float(Float(n)) => return write!(f, "{:?}", n),
+            TokenKind::Ident(name) => return write!(f, "{}", name),
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Asterisk => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::Caret => "^",
+            TokenKind::DoubleAsterisk => "**",
+            TokenKind::DoubleSlash => "//",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::Equal => "=",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::Newline => "\\n",
+            TokenKind::EqualEqual => "==",
+            TokenKind::NotEqual => "!=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::AndAnd => "&&",
+            TokenKind::OrOr => "||",
+            TokenKind::Bang => "!",
+            TokenKind::Let => "let",
+            TokenKind::Fn => "fn",
+            TokenKind::True => "true",
+            TokenKind::False => "false",
+            TokenKind::If => "if",
+            TokenKind::Then => "then",
+            TokenKind::Else => "else",
+        };
+        f.write_str(symbol)
+    }
+}
+
 macro_rules! annotation_factory {
     ($name: ident, $token: expr) => {
         pub fn $name(loc: Loc) -> Self {
@@ -56,17 +148,55 @@ impl Token {
     pub fn number(n: u64, loc: Loc) -> Self {
         Self::new(TokenKind::Number(n), loc)
     }
+    pub fn big_number(n: BigInt, loc: Loc) -> Self {
+        Self::new(TokenKind::BigNumber(n), loc)
+    }
+    pub fn float(f: f64, loc: Loc) -> Self {
+        Self::new(TokenKind::Float(Float(f)), loc)
+    }
     annotation_factory!(plus, TokenKind::Plus);
     annotation_factory!(minus, TokenKind::Minus);
     annotation_factory!(asterisk, TokenKind::Asterisk);
     annotation_factory!(slash, TokenKind::Slash);
+    annotation_factory!(percent, TokenKind::Percent);
+    annotation_factory!(caret, TokenKind::Caret);
+    annotation_factory!(double_asterisk, TokenKind::DoubleAsterisk);
+    annotation_factory!(double_slash, TokenKind::DoubleSlash);
     annotation_factory!(lparen, TokenKind::LParen);
     annotation_factory!(rparen, TokenKind::RParen);
+    annotation_factory!(equal, TokenKind::Equal);
+    annotation_factory!(comma, TokenKind::Comma);
+    annotation_factory!(semicolon, TokenKind::Semicolon);
+    annotation_factory!(newline, TokenKind::Newline);
+    annotation_factory!(equal_equal, TokenKind::EqualEqual);
+    annotation_factory!(not_equal, TokenKind::NotEqual);
+    annotation_factory!(less, TokenKind::Less);
+    annotation_factory!(less_equal, TokenKind::LessEqual);
+    annotation_factory!(greater, TokenKind::Greater);
+    annotation_factory!(greater_equal, TokenKind::GreaterEqual);
+    annotation_factory!(and_and, TokenKind::AndAnd);
+    annotation_factory!(or_or, TokenKind::OrOr);
+    annotation_factory!(bang, TokenKind::Bang);
+    pub fn ident(name: &str, loc: Loc) -> Self {
+        Self::new(TokenKind::Ident(name.to_string()), loc)
+    }
+    annotation_factory!(let_, TokenKind::Let);
+    annotation_factory!(fn_, TokenKind::Fn);
+    annotation_factory!(true_, TokenKind::True);
+    annotation_factory!(false_, TokenKind::False);
+    annotation_factory!(if_, TokenKind::If);
+    annotation_factory!(then_, TokenKind::Then);
+    annotation_factory!(else_, TokenKind::Else);
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LexErrorKind {
     InvalidChar(char),
+    /// integer literal that cannot be represented
+    LiteralOutOfRange,
+    /// float literal too large to be finite, e.g. `1e999`
+    FloatOutOfRange,
     EOF,
 }
 pub type LexError = Annotation<LexErrorKind>;
@@ -75,11 +205,19 @@ impl LexError {
     pub fn invalid_char(c: char, loc: Loc) -> Self {
         Self::new(LexErrorKind::InvalidChar(c), loc)
     }
+    annotation_factory!(literal_out_of_range, LexErrorKind::LiteralOutOfRange);
+    annotation_factory!(float_out_of_range, LexErrorKind::FloatOutOfRange);
     annotation_factory!(eof, LexErrorKind::EOF);
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct LexOptions {
+    /// represent integer literals beyond `u64` as `TokenKind::BigNumber` instead of rejecting them
+    pub big_integers: bool,
+}
+
 mod lex {
-    use super::{LexError, Loc, Token};
+    use super::{LexError, LexOptions, Loc, Token};
 
     pub type LexResult<T> = Result<(T, usize), LexError>;
 
@@ -93,14 +231,23 @@ mod lex {
         };
     }
 
+    macro_rules! lexer_fn2 {
+        ($name: ident, $first: expr, $second: expr) => {
+            pub fn $name(input_bytes: &[u8], position: usize) -> LexResult<Token> {
+                let (_, next) = consume_byte(input_bytes, position, $first)?;
+                // without its second byte, the first is not a token of its own
+                consume_byte(input_bytes, next, $second)
+                    .map(|(_, end)| (Token::$name(Loc::new(position, end)), end))
+                    .map_err(|_| invalid_char(input_bytes, position))
+            }
+        };
+    }
+
     fn consume_byte(input_bytes: &[u8], position: usize, expected: u8) -> LexResult<u8> {
         if input_bytes.len() <= position {
             Err(LexError::eof(Loc::new(position, position)))
         } else if input_bytes[position] != expected {
-            Err(LexError::invalid_char(
-                input_bytes[position] as char,
-                Loc::new(position, position),
-            ))
+            Err(invalid_char(input_bytes, position))
         } else {
             Ok((expected, position + 1))
         }
@@ -109,8 +256,25 @@ mod lex {
     lexer_fn!(minus, b'-');
     lexer_fn!(asterisk, b'*');
     lexer_fn!(slash, b'/');
+    lexer_fn!(percent, b'%');
+    lexer_fn!(caret, b'^');
+    lexer_fn2!(double_asterisk, b'*', b'*');
+    lexer_fn2!(double_slash, b'/', b'/');
     lexer_fn!(lparen, b'(');
     lexer_fn!(rparen, b')');
+    lexer_fn!(equal, b'=');
+    lexer_fn!(comma, b',');
+    lexer_fn!(semicolon, b';');
+    lexer_fn!(newline, b'\n');
+    lexer_fn!(less, b'<');
+    lexer_fn!(greater, b'>');
+    lexer_fn!(bang, b'!');
+    lexer_fn2!(equal_equal, b'=', b'=');
+    lexer_fn2!(not_equal, b'!', b'=');
+    lexer_fn2!(less_equal, b'<', b'=');
+    lexer_fn2!(greater_equal, b'>', b'=');
+    lexer_fn2!(and_and, b'&', b'&');
+    lexer_fn2!(or_or, b'|', b'|');
 
     fn consume_bytes_until(
         input_bytes: &[u8],
@@ -123,23 +287,100 @@ mod lex {
         position
     }
 
-    pub fn number(input_bytes: &[u8], position: usize) -> LexResult<Token> {
+    fn is_digit(input_bytes: &[u8], position: usize) -> bool {
+        position < input_bytes.len() && input_bytes[position].is_ascii_digit()
+    }
+
+    // number = digit+ ("." digit+)? (("e" | "E") ("+" | "-")? digit+)?
+    pub fn number(input_bytes: &[u8], position: usize, options: &LexOptions) -> LexResult<Token> {
         let start = position;
-        let end = consume_bytes_until(input_bytes, start, |byte| b"1234567890".contains(&byte));
-        let n = std::str::from_utf8(&input_bytes[start..end])
-            .unwrap()
-            .parse()
-            .unwrap();
-        Ok((Token::number(n, Loc::new(start, end)), end))
+        let mut end = consume_bytes_until(input_bytes, start, |byte| byte.is_ascii_digit());
+        let mut is_float = false;
+        if end < input_bytes.len() && input_bytes[end] == b'.' && is_digit(input_bytes, end + 1) {
+            end = consume_bytes_until(input_bytes, end + 1, |byte| byte.is_ascii_digit());
+            is_float = true;
+        }
+        if end < input_bytes.len() && (input_bytes[end] == b'e' || input_bytes[end] == b'E') {
+            let sign = end + 1 < input_bytes.len() && b"+-".contains(&input_bytes[end + 1]);
+            let digits_start = if sign { end + 2 } else { end + 1 };
+            if is_digit(input_bytes, digits_start) {
+                end = consume_bytes_until(input_bytes, digits_start, |byte| byte.is_ascii_digit());
+                is_float = true;
+            }
+        }
+        let literal = std::str::from_utf8(&input_bytes[start..end]).unwrap();
+        let loc = Loc::new(start, end);
+        let token = if is_float {
+            match literal.parse::<f64>() {
+                Ok(f) if f.is_finite() => Token::float(f, loc),
+                _ => return Err(LexError::float_out_of_range(loc)),
+            }
+        } else {
+            match literal.parse::<u64>() {
+                Ok(n) => Token::number(n, loc),
+                Err(_) if options.big_integers => Token::big_number(literal.parse().unwrap(), loc),
+                Err(_) => return Err(LexError::literal_out_of_range(loc)),
+            }
+        };
+        Ok((token, end))
+    }
+
+    // ident = (alphabet | "_") (alphabet | digit | "_")* ;
+    pub fn ident(input_bytes: &[u8], position: usize) -> LexResult<Token> {
+        let start = position;
+        let end = consume_bytes_until(input_bytes, start, |byte| {
+            byte.is_ascii_alphanumeric() || byte == b'_'
+        });
+        let loc = Loc::new(start, end);
+        let token = match std::str::from_utf8(&input_bytes[start..end]).unwrap() {
+            "let" => Token::let_(loc),
+            "fn" => Token::fn_(loc),
+            "true" => Token::true_(loc),
+            "false" => Token::false_(loc),
+            "if" => Token::if_(loc),
+            "then" => Token::then_(loc),
+            "else" => Token::else_(loc),
+            name => Token::ident(name, loc),
+        };
+        Ok((token, end))
+    }
+
+    // history = "$" digit+ ; lexed as an identifier bound by the REPL session
+    pub fn history(input_bytes: &[u8], position: usize) -> LexResult<Token> {
+        let start = position;
+        let end = consume_bytes_until(input_bytes, start + 1, |byte| byte.is_ascii_digit());
+        if end == start + 1 {
+            return Err(LexError::invalid_char('$', Loc::new(start, end)));
+        }
+        let name = std::str::from_utf8(&input_bytes[start..end]).unwrap();
+        Ok((Token::ident(name, Loc::new(start, end)), end))
     }
 
     pub fn ignore_spaces(input_bytes: &[u8], position: usize) -> LexResult<()> {
-        let end = consume_bytes_until(input_bytes, position, |byte| b" \n\t".contains(&byte));
+        let end = consume_bytes_until(input_bytes, position, |byte| b" \t\r".contains(&byte));
         Ok(((), end))
     }
+
+    /// Error for the character starting at `position`, which may span several bytes.
+    pub fn invalid_char(input_bytes: &[u8], position: usize) -> LexError {
+        let rest = &input_bytes[position..std::cmp::min(position + 4, input_bytes.len())];
+        let c = match std::str::from_utf8(rest) {
+            Ok(rest) => rest.chars().next(),
+            Err(e) => std::str::from_utf8(&rest[..e.valid_up_to()])
+                .ok()
+                .and_then(|rest| rest.chars().next()),
+        }
+        .unwrap_or(char::REPLACEMENT_CHARACTER);
+        let end = std::cmp::min(position + c.len_utf8(), input_bytes.len());
+        LexError::invalid_char(c, Loc::new(position, end))
+    }
 }
 
 pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
+    lex_with(input, &LexOptions::default())
+}
+
+pub fn lex_with(input: &str, options: &LexOptions) -> Result<Vec<Token>, LexError> {
     let mut tokens: Vec<Token> = Vec::new();
     let input_bytes = input.as_bytes();
     let mut position = 0;
@@ -153,23 +394,49 @@ pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
 
     while position < input_bytes.len() {
         match input_bytes[position] {
-            b'0'..=b'9' => lex_a_token!(lex::number(input_bytes, position)),
+            b'0'..=b'9' => lex_a_token!(lex::number(input_bytes, position, options)),
             b'+' => lex_a_token!(lex::plus(input_bytes, position)),
             b'-' => lex_a_token!(lex::minus(input_bytes, position)),
+            b'*' if input_bytes.get(position + 1) == Some(&b'*') => {
+                lex_a_token!(lex::double_asterisk(input_bytes, position))
+            }
             b'*' => lex_a_token!(lex::asterisk(input_bytes, position)),
+            b'/' if input_bytes.get(position + 1) == Some(&b'/') => {
+                lex_a_token!(lex::double_slash(input_bytes, position))
+            }
             b'/' => lex_a_token!(lex::slash(input_bytes, position)),
+            b'%' => lex_a_token!(lex::percent(input_bytes, position)),
+            b'^' => lex_a_token!(lex::caret(input_bytes, position)),
             b'(' => lex_a_token!(lex::lparen(input_bytes, position)),
             b')' => lex_a_token!(lex::rparen(input_bytes, position)),
-            b' ' | b'\n' | b'\t' => {
+            b'=' if input_bytes.get(position + 1) == Some(&b'=') => {
+                lex_a_token!(lex::equal_equal(input_bytes, position))
+            }
+            b'=' => lex_a_token!(lex::equal(input_bytes, position)),
+            b'!' if input_bytes.get(position + 1) == Some(&b'=') => {
+                lex_a_token!(lex::not_equal(input_bytes, position))
+            }
+            b'!' => lex_a_token!(lex::bang(input_bytes, position)),
+            b'<' if input_bytes.get(position + 1) == Some(&b'=') => {
+                lex_a_token!(lex::less_equal(input_bytes, position))
+            }
+            b'<' => lex_a_token!(lex::less(input_bytes, position)),
+            b'>' if input_bytes.get(position + 1) == Some(&b'=') => {
+                lex_a_token!(lex::greater_equal(input_bytes, position))
+            }
+            b'>' => lex_a_token!(lex::greater(input_bytes, position)),
+            b'&' => lex_a_token!(lex::and_and(input_bytes, position)),
+            b'|' => lex_a_token!(lex::or_or(input_bytes, position)),
+            b',' => lex_a_token!(lex::comma(input_bytes, position)),
+            b';' => lex_a_token!(lex::semicolon(input_bytes, position)),
+            b'\n' => lex_a_token!(lex::newline(input_bytes, position)),
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => lex_a_token!(lex::ident(input_bytes, position)),
+            b'$' => lex_a_token!(lex::history(input_bytes, position)),
+            b' ' | b'\t' | b'\r' => {
                 let ((), new_position) = lex::ignore_spaces(input_bytes, position)?;
                 position = new_position;
             }
-            b => {
-                return Err(LexError::invalid_char(
-                    b as char,
-                    Loc::new(position, position + 1),
-                ))
-            }
+            _ => return Err(lex::invalid_char(input_bytes, position)),
         }
     }
 
@@ -192,3 +459,103 @@ fn test_lexer() {
         ])
     )
 }
+
+#[test]
+fn test_lexer_float() {
+    assert_eq!(
+        lex("1.5 * 6.02e23 + 2E-3 + 7"),
+        Ok(vec![
+            Token::float(1.5, Loc::new(0, 3)),
+            Token::asterisk(Loc::new(4, 5)),
+            Token::float(6.02e23, Loc::new(6, 13)),
+            Token::plus(Loc::new(14, 15)),
+            Token::float(2e-3, Loc::new(16, 20)),
+            Token::plus(Loc::new(21, 22)),
+            Token::number(7, Loc::new(23, 24)),
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_big_number() {
+    let input = "1 + 99999999999999999999999";
+    assert_eq!(
+        lex(input),
+        Err(LexError::literal_out_of_range(Loc::new(4, 27)))
+    );
+    assert_eq!(
+        lex_with(input, &LexOptions { big_integers: true }),
+        Ok(vec![
+            Token::number(1, Loc::new(0, 1)),
+            Token::plus(Loc::new(2, 3)),
+            Token::big_number("99999999999999999999999".parse().unwrap(), Loc::new(4, 27)),
+        ])
+    );
+    assert_eq!(
+        lex("1e999"),
+        Err(LexError::float_out_of_range(Loc::new(0, 5)))
+    );
+}
+
+#[test]
+fn test_lexer_let() {
+    assert_eq!(
+        lex("let rate_2 = x1*2"),
+        Ok(vec![
+            Token::let_(Loc::new(0, 3)),
+            Token::ident("rate_2", Loc::new(4, 10)),
+            Token::equal(Loc::new(11, 12)),
+            Token::ident("x1", Loc::new(13, 15)),
+            Token::asterisk(Loc::new(15, 16)),
+            Token::number(2, Loc::new(16, 17)),
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_operators() {
+    assert_eq!(
+        lex("7%2**3^4//5*/"),
+        Ok(vec![
+            Token::number(7, Loc::new(0, 1)),
+            Token::percent(Loc::new(1, 2)),
+            Token::number(2, Loc::new(2, 3)),
+            Token::double_asterisk(Loc::new(3, 5)),
+            Token::number(3, Loc::new(5, 6)),
+            Token::caret(Loc::new(6, 7)),
+            Token::number(4, Loc::new(7, 8)),
+            Token::double_slash(Loc::new(8, 10)),
+            Token::number(5, Loc::new(10, 11)),
+            Token::asterisk(Loc::new(11, 12)),
+            Token::slash(Loc::new(12, 13)),
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_comparison() {
+    assert_eq!(
+        lex("!a<=b==true||c!=d&&e>f"),
+        Ok(vec![
+            Token::bang(Loc::new(0, 1)),
+            Token::ident("a", Loc::new(1, 2)),
+            Token::less_equal(Loc::new(2, 4)),
+            Token::ident("b", Loc::new(4, 5)),
+            Token::equal_equal(Loc::new(5, 7)),
+            Token::true_(Loc::new(7, 11)),
+            Token::or_or(Loc::new(11, 13)),
+            Token::ident("c", Loc::new(13, 14)),
+            Token::not_equal(Loc::new(14, 16)),
+            Token::ident("d", Loc::new(16, 17)),
+            Token::and_and(Loc::new(17, 19)),
+            Token::ident("e", Loc::new(19, 20)),
+            Token::greater(Loc::new(20, 21)),
+            Token::ident("f", Loc::new(21, 22)),
+        ])
+    );
+    assert_eq!(
+        lex("a & b"),
+        Err(LexError::invalid_char('&', Loc::new(2, 3)))
+    );
+    assert_eq!(lex("a |"), Err(LexError::invalid_char('|', Loc::new(2, 3))));
+}
@@ -0,0 +1,468 @@
+//! Infers the type of every node of a tree before it is evaluated, and reports each place
+//! that is certain to fail with a type error, e.g. `1 + true` or `if 1 then 2 else 3`,
+//! instead of only the first one that evaluation reaches.
+//!
+//! A variable bound neither in the tree nor in the given environment is an input of unknown
+//! type, as are the parameters of functions, so the checker only rejects what fails whatever
+//! their values. Built-in functions take and return numbers.
+use crate::{
+    ast::{Ast, AstKind, BinOperatorKind, Identifier, ParseOptions, UniOperatorKind},
+    builtins::{self, Builtin},
+    interpreter::Environment,
+    token::Annotation,
+    value::{Type, Value},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeErrorKind {
+    Mismatch {
+        expected: Type,
+        found: Type,
+    },
+    ArityMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// the tree is nested deeper than any the parser builds, so it is not checked any deeper
+    NestingTooDeep,
+}
+pub type TypeError = Annotation<TypeErrorKind>;
+
+/// The inferred type of a node, `None` when it depends on inputs, with the types of its
+/// children in the order `visit::walk_ast` visits them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Typed {
+    pub ty: Option<Type>,
+    pub children: Vec<TypedAst>,
+}
+pub type TypedAst = Annotation<Typed>;
+
+/// Annotates `ast` with types, reporting every type error in it.
+pub fn check(ast: &Ast) -> (TypedAst, Vec<TypeError>) {
+    check_with(ast, &Environment::new())
+}
+
+/// `check` with the bindings of `env` in scope, as `interpreter::eval_with` has them.
+pub fn check_with(ast: &Ast, env: &Environment) -> (TypedAst, Vec<TypeError>) {
+    check_with_options(ast, env, &ParseOptions::default())
+}
+
+/// `check_with` for a tree parsed with `options`, as deep as they let it be.
+pub fn check_with_options(
+    ast: &Ast,
+    env: &Environment,
+    options: &ParseOptions,
+) -> (TypedAst, Vec<TypeError>) {
+    let mut checker = Checker {
+        globals: env,
+        scopes: vec![],
+        errors: vec![],
+        depth: 0,
+        max_depth: options.max_depth + options.max_tree_depth,
+    };
+    let (typed, _) = checker.check(ast);
+    (typed, checker.errors)
+}
+
+// what is known of a value: its type and, for functions, how to call them
+#[derive(Debug, Clone, Default)]
+struct Known {
+    ty: Option<Type>,
+    signature: Option<Signature>,
+}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    min_arity: usize,
+    /// `None` for variadic functions
+    max_arity: Option<usize>,
+    /// the type of every parameter, `None` if unknown
+    params: Option<Type>,
+    returns: Option<Type>,
+}
+
+impl Known {
+    fn of(ty: Type) -> Self {
+        Self {
+            ty: Some(ty),
+            signature: None,
+        }
+    }
+
+    fn function(signature: Signature) -> Self {
+        Self {
+            ty: Some(Type::Function),
+            signature: Some(signature),
+        }
+    }
+
+    fn builtin(builtin: &Builtin) -> Self {
+        Self::function(Signature {
+            min_arity: builtin.min_arity,
+            max_arity: builtin.max_arity,
+            params: Some(Type::Number),
+            returns: Some(Type::Number),
+        })
+    }
+
+    fn value(value: &Value) -> Self {
+        match value {
+            Value::Function(function) => Self::function(Signature::user(function.params.len())),
+            Value::Builtin(builtin) => Self::builtin(builtin),
+            value => Self::of(value.type_of()),
+        }
+    }
+}
+
+impl Signature {
+    fn user(arity: usize) -> Self {
+        Self {
+            min_arity: arity,
+            max_arity: Some(arity),
+            params: None,
+            returns: None,
+        }
+    }
+}
+
+struct Checker<'a> {
+    globals: &'a Environment,
+    /// bindings made by the tree so far, the latest last
+    scopes: Vec<(String, Known)>,
+    errors: Vec<TypeError>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Checker<'_> {
+    fn check(&mut self, ast: &Ast) -> (TypedAst, Known) {
+        use BinOperatorKind::*;
+        let mut children = vec![];
+        let known = match &ast.value {
+            AstKind::Number(_) | AstKind::BigNumber(_) | AstKind::Float(_) => {
+                Known::of(Type::Number)
+            }
+            AstKind::Bool(_) => Known::of(Type::Bool),
+            AstKind::Error => Known::default(),
+            AstKind::UniOperator { operator, tree } => {
+                let operand = self.child(tree, &mut children).ty;
+                let ty = match operator.value {
+                    UniOperatorKind::Plus | UniOperatorKind::Minus => Type::Number,
+                    UniOperatorKind::Not => Type::Bool,
+                };
+                self.expect(operand, ty, tree);
+                Known::of(ty)
+            }
+            AstKind::BinOperator { operator, lhs, rhs } => {
+                let left = self.child(lhs, &mut children).ty;
+                let right = self.child(rhs, &mut children).ty;
+                let (operands, ty) = match operator.value {
+                    And | Or => (Some(Type::Bool), Type::Bool),
+                    Lt | Le | Gt | Ge => (Some(Type::Number), Type::Bool),
+                    // both booleans or both numbers
+                    Eq | Ne => (None, Type::Bool),
+                    Add | Sub | Mul | Div | Mod | Pow | FloorDiv => {
+                        (Some(Type::Number), Type::Number)
+                    }
+                };
+                match (operands, left) {
+                    (Some(operands), _) => {
+                        self.expect(left, operands, lhs);
+                        self.expect(right, operands, rhs);
+                    }
+                    (None, Some(Type::Function)) => self.expect(left, Type::Number, lhs),
+                    (None, Some(left)) => self.expect(right, left, rhs),
+                    (None, None) => self.expect(
+                        right.filter(|right| *right == Type::Function),
+                        Type::Number,
+                        rhs,
+                    ),
+                }
+                Known::of(ty)
+            }
+            AstKind::Variable(name) => self.lookup(name),
+            AstKind::Let { name, value } => {
+                let known = self.child(value, &mut children);
+                self.scopes.push((name.value.clone(), known.clone()));
+                known
+            }
+            AstKind::FnDef { name, params, body } => {
+                // the body sees the function itself, of a yet unknown return type
+                let signature = Signature::user(params.len());
+                self.scopes
+                    .push((name.value.clone(), Known::function(signature)));
+                let known = self.function(params, body, &mut children);
+                self.scopes.push((name.value.clone(), known.clone()));
+                known
+            }
+            AstKind::Lambda { params, body } => self.function(params, body, &mut children),
+            AstKind::Call { callee, args } => {
+                let function = self.child(callee, &mut children);
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| (arg, self.child(arg, &mut children).ty))
+                    .collect();
+                self.expect(function.ty, Type::Function, callee);
+                match function.signature {
+                    Some(signature) => {
+                        self.check_arity(&signature, args.len(), ast);
+                        if let Some(param) = signature.params {
+                            for (arg, ty) in args {
+                                self.expect(ty, param, arg);
+                            }
+                        }
+                        Known {
+                            ty: signature.returns,
+                            signature: None,
+                        }
+                    }
+                    None => Known::default(),
+                }
+            }
+            AstKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                let cond_ty = self.child(cond, &mut children).ty;
+                self.expect(cond_ty, Type::Bool, cond);
+                let then_ty = self.child(then_branch, &mut children).ty;
+                let else_ty = self.child(else_branch, &mut children).ty;
+                Known {
+                    ty: then_ty.filter(|then_ty| Some(*then_ty) == else_ty),
+                    signature: None,
+                }
+            }
+            AstKind::Program(stmts) => stmts
+                .iter()
+                .map(|stmt| self.child(stmt, &mut children))
+                .last()
+                .unwrap_or_default(),
+        };
+        let typed = Typed {
+            ty: known.ty,
+            children,
+        };
+        (TypedAst::new(typed, ast.loc.clone()), known)
+    }
+
+    // a child one level deeper, left unchecked past the maximum depth
+    fn child(&mut self, ast: &Ast, children: &mut Vec<TypedAst>) -> Known {
+        if self.depth == self.max_depth {
+            if !self
+                .errors
+                .iter()
+                .any(|error| error.value == TypeErrorKind::NestingTooDeep)
+            {
+                self.errors.push(TypeError::new(
+                    TypeErrorKind::NestingTooDeep,
+                    ast.loc.clone(),
+                ));
+            }
+            children.push(TypedAst::new(
+                Typed {
+                    ty: None,
+                    children: vec![],
+                },
+                ast.loc.clone(),
+            ));
+            return Known::default();
+        }
+        self.depth += 1;
+        let (typed, known) = self.check(ast);
+        self.depth -= 1;
+        children.push(typed);
+        known
+    }
+
+    // the body is checked in a scope of parameters of unknown types
+    fn function(
+        &mut self,
+        params: &[Identifier],
+        body: &Ast,
+        children: &mut Vec<TypedAst>,
+    ) -> Known {
+        let outer = self.scopes.len();
+        for param in params {
+            self.scopes.push((param.value.clone(), Known::default()));
+        }
+        let returns = self.child(body, children).ty;
+        self.scopes.truncate(outer);
+        Known::function(Signature {
+            returns,
+            ..Signature::user(params.len())
+        })
+    }
+
+    // bindings shadow built-in functions, as in `interpreter`
+    fn lookup(&self, name: &str) -> Known {
+        match self.scopes.iter().rev().find(|(bound, _)| bound == name) {
+            Some((_, known)) => known.clone(),
+            None => match self.globals.get(name) {
+                Some(value) => Known::value(value),
+                None => builtins::lookup(name).map_or_else(Known::default, Known::builtin),
+            },
+        }
+    }
+
+    fn expect(&mut self, found: Option<Type>, expected: Type, ast: &Ast) {
+        match found {
+            Some(found) if found != expected => self.errors.push(TypeError::new(
+                TypeErrorKind::Mismatch { expected, found },
+                ast.loc.clone(),
+            )),
+            _ => {}
+        }
+    }
+
+    fn check_arity(&mut self, signature: &Signature, found: usize, call: &Ast) {
+        let expected = match signature.max_arity {
+            _ if found < signature.min_arity => signature.min_arity,
+            Some(max_arity) if max_arity < found => max_arity,
+            _ => return,
+        };
+        self.errors.push(TypeError::new(
+            TypeErrorKind::ArityMismatch { expected, found },
+            call.loc.clone(),
+        ));
+    }
+}
+
+#[test]
+fn test_check() {
+    use crate::{
+        token::Loc,
+        visit::{walk_ast, Visitor},
+    };
+
+    // the nodes of a tree and their types, both in the order `walk_ast` visits them
+    fn types(typed: &TypedAst) -> Vec<Option<Type>> {
+        let mut all = vec![typed.value.ty];
+        for child in &typed.value.children {
+            all.extend(types(child));
+        }
+        all
+    }
+    struct Nodes(Vec<String>);
+    impl Visitor for Nodes {
+        fn visit_ast(&mut self, ast: &Ast) {
+            self.0.push(ast.to_string());
+            walk_ast(self, ast);
+        }
+    }
+    let nodes = |ast: &Ast| {
+        let mut nodes = Nodes(vec![]);
+        nodes.visit_ast(ast);
+        nodes.0
+    };
+
+    let errors = |source: &str| check(&source.parse().unwrap()).1;
+    let mismatch = |expected, found, start, end| {
+        TypeError::new(
+            TypeErrorKind::Mismatch { expected, found },
+            Loc::new(start, end),
+        )
+    };
+    assert_eq!(errors("fn f(x) = x * 2; f(3) + abs(-1)"), vec![]);
+    assert_eq!(errors("if x then y else z"), vec![]);
+    // every error, not only the first
+    assert_eq!(
+        errors("(1 + true) * (!2 || 3 < false)"),
+        vec![
+            mismatch(Type::Number, Type::Bool, 5, 9),
+            mismatch(Type::Bool, Type::Number, 15, 16),
+            mismatch(Type::Number, Type::Bool, 24, 29),
+            mismatch(Type::Number, Type::Bool, 14, 29),
+        ]
+    );
+    assert_eq!(
+        errors("let n = 1; if n then n(2) else n == true"),
+        vec![
+            mismatch(Type::Bool, Type::Number, 14, 15),
+            mismatch(Type::Function, Type::Number, 21, 22),
+            mismatch(Type::Number, Type::Bool, 36, 40),
+        ]
+    );
+    assert_eq!(
+        errors("fn f(x) = x; f(1, 2) + max() + sqrt(1 > 0)"),
+        vec![
+            TypeError::new(
+                TypeErrorKind::ArityMismatch {
+                    expected: 1,
+                    found: 2
+                },
+                Loc::new(13, 20)
+            ),
+            TypeError::new(
+                TypeErrorKind::ArityMismatch {
+                    expected: 1,
+                    found: 0
+                },
+                Loc::new(23, 28)
+            ),
+            mismatch(Type::Number, Type::Bool, 36, 41),
+        ]
+    );
+    // inferred return types flow into calls, lambdas included
+    assert_eq!(
+        errors("let pos = fn(x) = x > 0; pos(1) + 1"),
+        vec![mismatch(Type::Number, Type::Bool, 25, 31)]
+    );
+    // bindings of the environment are in scope
+    let mut env = Environment::new();
+    env.set("b", Value::Bool(true));
+    let ast = "-b".parse().unwrap();
+    assert_eq!(
+        check_with(&ast, &env).1,
+        vec![mismatch(Type::Number, Type::Bool, 1, 2)]
+    );
+
+    let ast: Ast = "let f = fn(x) = x; if f(1) == 2 then f else sin"
+        .parse()
+        .unwrap();
+    let (typed, errors) = check(&ast);
+    assert_eq!(errors, vec![]);
+    let annotated: Vec<_> = nodes(&ast).into_iter().zip(types(&typed)).collect();
+    assert_eq!(
+        annotated[..4],
+        [
+            (
+                "let f = fn(x) = x; if f(1) == 2 then f else sin".to_string(),
+                Some(Type::Function)
+            ),
+            ("let f = fn(x) = x".to_string(), Some(Type::Function)),
+            ("fn(x) = x".to_string(), Some(Type::Function)),
+            ("x".to_string(), None),
+        ]
+    );
+    assert_eq!(
+        annotated[5..],
+        [
+            ("f(1) == 2".to_string(), Some(Type::Bool)),
+            ("f(1)".to_string(), None),
+            ("f".to_string(), Some(Type::Function)),
+            ("1".to_string(), Some(Type::Number)),
+            ("2".to_string(), Some(Type::Number)),
+            ("f".to_string(), Some(Type::Function)),
+            ("sin".to_string(), Some(Type::Function)),
+        ]
+    );
+
+    // a tree deeper than the parser builds is only checked down to the limit, reported once
+    let env = Environment::new();
+    let mut checker = Checker {
+        globals: &env,
+        scopes: vec![],
+        errors: vec![],
+        depth: 0,
+        max_depth: 2,
+    };
+    checker.check(&"1 + 2 * true - 4 * false".parse().unwrap());
+    assert_eq!(
+        checker.errors,
+        vec![
+            TypeError::new(TypeErrorKind::NestingTooDeep, Loc::new(4, 5)),
+            mismatch(Type::Number, Type::Bool, 19, 24),
+        ]
+    );
+}
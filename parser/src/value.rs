@@ -0,0 +1,123 @@
+use std::rc::Rc;
+
+use crate::{
+    ast::Ast, bigint::BigInt, builtins::Builtin, interpreter::Environment, rational::Rational,
+};
+
+/// Result of evaluating an expression.
+///
+/// Integers and floats coexist: an operation between an `Int` and a `Float`
+/// promotes the `Int` to `Float`, while an operation between two `Int`s stays
+/// an `Int` (so `Int / Int` truncates). A `Rational` promotes an `Int` to
+/// `Rational` and is itself promoted to `Float`, and so does a `BigInt`.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    /// exact fraction, what integer literals evaluate to in `NumberMode::Rational`
+    Rational(Rational),
+    /// integer of any size, what integer literals evaluate to in `NumberMode::BigInt`
+    BigInt(BigInt),
+    Bool(bool),
+    Function(Rc<Function>),
+    Builtin(&'static Builtin),
+}
+
+/// A user-defined function or lambda, closed over the environment it was defined in.
+#[derive(Debug)]
+pub struct Function {
+    /// `None` for lambdas; named functions can call themselves through it
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Rc<Ast>,
+    pub env: Environment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Number,
+    Bool,
+    Function,
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(num) => Some(*num as f64),
+            Value::Float(num) => Some(*num),
+            Value::Rational(num) => Some(num.to_f64()),
+            Value::BigInt(num) => Some(num.to_f64()),
+            Value::Bool(_) | Value::Function(_) | Value::Builtin(_) => None,
+        }
+    }
+
+    /// `Int`s and `Rational`s, which combine exactly.
+    pub fn as_rational(&self) -> Option<Rational> {
+        match self {
+            Value::Int(num) => Some(Rational::from(*num)),
+            Value::Rational(num) => Some(*num),
+            _ => None,
+        }
+    }
+
+    /// `Int`s and `BigInt`s, which combine exactly.
+    pub fn as_big_int(&self) -> Option<BigInt> {
+        match self {
+            Value::Int(num) => Some(BigInt::from(*num)),
+            Value::BigInt(num) => Some(num.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::Int(_) | Value::Float(_) | Value::Rational(_) | Value::BigInt(_) => Type::Number,
+            Value::Bool(_) => Type::Bool,
+            Value::Function(_) | Value::Builtin(_) => Type::Function,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => left == right,
+            (Value::Rational(left), Value::Rational(right)) => left == right,
+            (Value::BigInt(left), Value::BigInt(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Builtin(left), Value::Builtin(right)) => std::ptr::eq(*left, *right),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(num) => write!(f, "{}", num),
+            Value::Float(num) => write!(f, "{:?}", num),
+            Value::Rational(num) => write!(f, "{}", num),
+            Value::BigInt(num) => write!(f, "{}", num),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Function(function) => write!(
+                f,
+                "<fn {}({})>",
+                function.name.as_deref().unwrap_or(""),
+                function.params.join(", ")
+            ),
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::Bool => write!(f, "bool"),
+            Type::Function => write!(f, "function"),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Traversals over `Ast`. Each trait method defaults to walking into the children, so an
+//! implementation overrides only the nodes it cares about and calls the `walk_*`/`fold_*`
+//! function to keep descending.
+use crate::ast::{Ast, AstKind, BinOperator, Identifier, UniOperator};
+
+/// Visits a tree by reference, e.g. to count nodes or collect variables.
+pub trait Visitor {
+    fn visit_ast(&mut self, ast: &Ast) {
+        walk_ast(self, ast)
+    }
+    fn visit_uni_operator(&mut self, _operator: &UniOperator) {}
+    fn visit_bin_operator(&mut self, _operator: &BinOperator) {}
+    /// names bound by `let`, `fn` and parameters; variable uses are `AstKind::Variable`
+    fn visit_identifier(&mut self, _identifier: &Identifier) {}
+}
+
+/// Visits the children of `ast`, in source order.
+pub fn walk_ast<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast) {
+    match &ast.value {
+        AstKind::Number(_)
+        | AstKind::BigNumber(_)
+        | AstKind::Float(_)
+        | AstKind::Bool(_)
+        | AstKind::Variable(_)
+        | AstKind::Error => {}
+        AstKind::UniOperator { operator, tree } => {
+            visitor.visit_uni_operator(operator);
+            visitor.visit_ast(tree);
+        }
+        AstKind::BinOperator { operator, lhs, rhs } => {
+            visitor.visit_ast(lhs);
+            visitor.visit_bin_operator(operator);
+            visitor.visit_ast(rhs);
+        }
+        AstKind::Let { name, value } => {
+            visitor.visit_identifier(name);
+            visitor.visit_ast(value);
+        }
+        AstKind::FnDef { name, params, body } => {
+            visitor.visit_identifier(name);
+            params
+                .iter()
+                .for_each(|param| visitor.visit_identifier(param));
+            visitor.visit_ast(body);
+        }
+        AstKind::Lambda { params, body } => {
+            params
+                .iter()
+                .for_each(|param| visitor.visit_identifier(param));
+            visitor.visit_ast(body);
+        }
+        AstKind::Call { callee, args } => {
+            visitor.visit_ast(callee);
+            args.iter().for_each(|arg| visitor.visit_ast(arg));
+        }
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_ast(cond);
+            visitor.visit_ast(then_branch);
+            visitor.visit_ast(else_branch);
+        }
+        AstKind::Program(stmts) => stmts.iter().for_each(|stmt| visitor.visit_ast(stmt)),
+    }
+}
+
+/// Visits a tree by mutable reference, to rewrite it in place.
+pub trait VisitorMut {
+    fn visit_ast_mut(&mut self, ast: &mut Ast) {
+        walk_ast_mut(self, ast)
+    }
+    fn visit_uni_operator_mut(&mut self, _operator: &mut UniOperator) {}
+    fn visit_bin_operator_mut(&mut self, _operator: &mut BinOperator) {}
+    fn visit_identifier_mut(&mut self, _identifier: &mut Identifier) {}
+}
+
+pub fn walk_ast_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast) {
+    match &mut ast.value {
+        AstKind::Number(_)
+        | AstKind::BigNumber(_)
+        | AstKind::Float(_)
+        | AstKind::Bool(_)
+        | AstKind::Variable(_)
+        | AstKind::Error => {}
+        AstKind::UniOperator { operator, tree } => {
+            visitor.visit_uni_operator_mut(operator);
+            visitor.visit_ast_mut(tree);
+        }
+        AstKind::BinOperator { operator, lhs, rhs } => {
+            visitor.visit_ast_mut(lhs);
+            visitor.visit_bin_operator_mut(operator);
+            visitor.visit_ast_mut(rhs);
+        }
+        AstKind::Let { name, value } => {
+            visitor.visit_identifier_mut(name);
+            visitor.visit_ast_mut(value);
+        }
+        AstKind::FnDef { name, params, body } => {
+            visitor.visit_identifier_mut(name);
+            params
+                .iter_mut()
+                .for_each(|param| visitor.visit_identifier_mut(param));
+            visitor.visit_ast_mut(body);
+        }
+        AstKind::Lambda { params, body } => {
+            params
+                .iter_mut()
+                .for_each(|param| visitor.visit_identifier_mut(param));
+            visitor.visit_ast_mut(body);
+        }
+        AstKind::Call { callee, args } => {
+            visitor.visit_ast_mut(callee);
+            args.iter_mut().for_each(|arg| visitor.visit_ast_mut(arg));
+        }
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_ast_mut(cond);
+            visitor.visit_ast_mut(then_branch);
+            visitor.visit_ast_mut(else_branch);
+        }
+        AstKind::Program(stmts) => stmts
+            .iter_mut()
+            .for_each(|stmt| visitor.visit_ast_mut(stmt)),
+    }
+}
+
+/// Consumes a tree and builds a new one, children first.
+pub trait Fold {
+    fn fold_ast(&mut self, ast: Ast) -> Ast {
+        fold_ast(self, ast)
+    }
+    fn fold_uni_operator(&mut self, operator: UniOperator) -> UniOperator {
+        operator
+    }
+    fn fold_bin_operator(&mut self, operator: BinOperator) -> BinOperator {
+        operator
+    }
+    fn fold_identifier(&mut self, identifier: Identifier) -> Identifier {
+        identifier
+    }
+}
+
+/// Folds the children of `ast` and rebuilds it at the same location.
+pub fn fold_ast<F: Fold + ?Sized>(folder: &mut F, ast: Ast) -> Ast {
+    let value = match ast.value {
+        kind @ AstKind::Number(_)
+        | kind @ AstKind::BigNumber(_)
+        | kind @ AstKind::Float(_)
+        | kind @ AstKind::Bool(_)
+        | kind @ AstKind::Variable(_)
+        | kind @ AstKind::Error => kind,
+        AstKind::UniOperator { operator, tree } => AstKind::UniOperator {
+            operator: folder.fold_uni_operator(operator),
+            tree: Box::new(folder.fold_ast(*tree)),
+        },
+        AstKind::BinOperator { operator, lhs, rhs } => {
+            let lhs = Box::new(folder.fold_ast(*lhs));
+            let operator = folder.fold_bin_operator(operator);
+            let rhs = Box::new(folder.fold_ast(*rhs));
+            AstKind::BinOperator { operator, lhs, rhs }
+        }
+        AstKind::Let { name, value } => AstKind::Let {
+            name: folder.fold_identifier(name),
+            value: Box::new(folder.fold_ast(*value)),
+        },
+        AstKind::FnDef { name, params, body } => AstKind::FnDef {
+            name: folder.fold_identifier(name),
+            params: fold_identifiers(folder, params),
+            body: Box::new(folder.fold_ast(*body)),
+        },
+        AstKind::Lambda { params, body } => AstKind::Lambda {
+            params: fold_identifiers(folder, params),
+            body: Box::new(folder.fold_ast(*body)),
+        },
+        AstKind::Call { callee, args } => AstKind::Call {
+            callee: Box::new(folder.fold_ast(*callee)),
+            args: args.into_iter().map(|arg| folder.fold_ast(arg)).collect(),
+        },
+        AstKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => AstKind::If {
+            cond: Box::new(folder.fold_ast(*cond)),
+            then_branch: Box::new(folder.fold_ast(*then_branch)),
+            else_branch: Box::new(folder.fold_ast(*else_branch)),
+        },
+        AstKind::Program(stmts) => AstKind::Program(
+            stmts
+                .into_iter()
+                .map(|stmt| folder.fold_ast(stmt))
+                .collect(),
+        ),
+    };
+    Ast::new(value, ast.loc)
+}
+
+fn fold_identifiers<F: Fold + ?Sized>(
+    folder: &mut F,
+    identifiers: Vec<Identifier>,
+) -> Vec<Identifier> {
+    identifiers
+        .into_iter()
+        .map(|identifier| folder.fold_identifier(identifier))
+        .collect()
+}
+
+#[test]
+fn test_visitors() {
+    use crate::ast::{BinOperatorKind, UniOperatorKind};
+    use crate::token::Float;
+
+    // node count, depth and variable use
+    #[derive(Default)]
+    struct Stats {
+        nodes: usize,
+        depth: usize,
+        max_depth: usize,
+        variables: Vec<String>,
+    }
+    impl Visitor for Stats {
+        fn visit_ast(&mut self, ast: &Ast) {
+            self.nodes += 1;
+            self.depth += 1;
+            self.max_depth = std::cmp::max(self.max_depth, self.depth);
+            if let AstKind::Variable(name) = &ast.value {
+                self.variables.push(name.clone());
+            }
+            walk_ast(self, ast);
+            self.depth -= 1;
+        }
+    }
+    let ast: Ast = "fn f(x) = -x * (y + 1)".parse().unwrap();
+    let mut stats = Stats::default();
+    stats.visit_ast(&ast);
+    assert_eq!(stats.nodes, 7);
+    assert_eq!(stats.max_depth, 4);
+    assert_eq!(stats.variables, vec!["x", "y"]);
+
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_ast_mut(&mut self, ast: &mut Ast) {
+            if let AstKind::Variable(name) = &mut ast.value {
+                name.make_ascii_uppercase();
+            }
+            walk_ast_mut(self, ast);
+        }
+        fn visit_identifier_mut(&mut self, identifier: &mut Identifier) {
+            identifier.value.make_ascii_uppercase();
+        }
+    }
+    let mut renamed = ast.clone();
+    Rename.visit_ast_mut(&mut renamed);
+    assert_eq!(renamed.to_string(), "fn F(X) = -X * (Y + 1)");
+
+    // integers to floats, `-` to `+`
+    struct Floats;
+    impl Fold for Floats {
+        fn fold_ast(&mut self, ast: Ast) -> Ast {
+            match ast.value {
+                AstKind::Number(n) => Ast::new(AstKind::Float(Float(n as f64)), ast.loc),
+                _ => fold_ast(self, ast),
+            }
+        }
+        fn fold_uni_operator(&mut self, operator: UniOperator) -> UniOperator {
+            UniOperator::new(UniOperatorKind::Plus, operator.loc)
+        }
+        fn fold_bin_operator(&mut self, operator: BinOperator) -> BinOperator {
+            match operator.value {
+                BinOperatorKind::Sub => BinOperator::new(BinOperatorKind::Add, operator.loc),
+                _ => operator,
+            }
+        }
+    }
+    let folded = Floats.fold_ast("-x - 2 * 3".parse().unwrap());
+    assert_eq!(folded.to_string(), "+x + 2.0 * 3.0");
+    assert_eq!(folded.loc, crate::token::Loc::new(0, 10));
+}